@@ -0,0 +1,720 @@
+use n_crypt::core::{
+   Identity, Options, Overwrite, Preset, Progress, Recipient, RecoveryKey, Secret,
+   age_format::{self, AgeSecret, AgeTarget},
+   batch::rekey_batch,
+   decrypt_file, derive_key, encrypt_dir_with_key, encrypt_file_with_key, inspect,
+   keyfile::{self, Keyfile},
+   output::write_file,
+   rekey,
+   shamir::{self, Share},
+   slots,
+   stream::DataKey,
+};
+use ncrypt_me::{Argon2, Credentials, secure_types::SecureString};
+use std::io::{IsTerminal, Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use zeroize::Zeroize;
+
+const USAGE: &str = "Usage: ncrypt-cli <COMMAND> <FILE> [OPTIONS]
+
+Commands:
+   encrypt        Encrypt FILE (or a whole directory) to FILE.ncrypt
+   decrypt        Decrypt FILE.ncrypt under the name it was encrypted with
+   inspect        Show the format and Argon2 parameters an encrypted FILE was made with
+   keygen         Generate an identity, save it to FILE encrypted with the credentials and print its public key
+   slots          List the passwords and recipients that can open FILE
+   add-password   Let another username and password open FILE, the credentials (or --identity) unlock it first
+   add-recipient  Let the owner of --recipient open FILE, the credentials (or --identity) unlock it first
+   remove-slot    Remove slot --slot from FILE, the credentials (or --identity) must open one of the others
+   change-password
+                  Replace the password that opens FILE with a new one, without writing the plaintext to disk
+   scan           List the .ncrypt files in the folder FILE whose Argon2 parameters are below the Argon2 options
+   upgrade        Re-key the files scan finds with the Argon2 options, asking for the credentials once
+
+Options:
+   --username <NAME>       Username, prompted for if omitted
+   --password-env <VAR>    Read the password from the environment variable VAR
+   --password-fd <FD>      Read the password from the file descriptor FD (Unix only)
+   --keyfile <PATH>        Mix the keyfile at PATH into the password, may be given more than once
+   --recipient <KEY>       Encrypt to the public KEY instead of a password, may be given more than once
+   --identity <PATH>       Decrypt with the identity at PATH, the credentials unlock the identity
+   --new-username <NAME>   Username of the new password, prompted for if omitted
+   --new-password-env <VAR>
+                           Read the new password from the environment variable VAR
+   --new-keyfile <PATH>    Mix the keyfile at PATH into the new password of change-password
+   --slot <N>              The slot remove-slot removes, as numbered by the slots command
+   --shares <K>/<N>        Also let a recovery key open the file and print it split into N shares, any K of which
+                           decrypt it (encrypt only)
+   --share <SHARE>         Decrypt with shares instead of the credentials, given once per share
+   --recovery-key          Also let a recovery key open the file and print it as 24 words (encrypt only)
+   --recovery-sheet <PATH> Like --recovery-key and also save a printable sheet with its QR code to PATH as SVG
+   --recovery              The password given is the recovery key of the file (decrypt only)
+   --age                   Write and read the age format, the password is the passphrase and --recipient and
+                           --identity take age1 keys and age-keygen identity files
+   --preset <NAME>         Argon2 preset: fast, balanced (default), strong or paranoid (encrypt and new passwords)
+   --m-cost <KiB>          Argon2 memory cost in KiB (encrypt and new passwords)
+   --t-cost <N>            Argon2 iterations (encrypt and new passwords)
+   --p-cost <N>            Argon2 parallelism (encrypt and new passwords)
+   --output <PATH>         Write the result to PATH instead of next to FILE
+   --hide-name             Give the encrypted file a random name (encrypt only)
+   --verify                Decrypt the encrypted file again and compare it to FILE (encrypt only)
+   --shred                 Verify the encrypted file, then overwrite and remove FILE (encrypt only)
+   --ignore-memory-limit   Run Argon2 even if it needs more memory than is available
+   --overwrite             Replace the output if it already exists
+   --keep-both             Write next to an existing output under a new name
+
+If neither --password-env nor --password-fd is given the password is prompted for on the terminal.
+Encrypting to recipients asks for no credentials at all. The password added by add-password uses the keyfiles
+given with --keyfile and the Argon2 options, all passwords of a file either use keyfiles or not.
+change-password keeps the Argon2 parameters of the old password unless any Argon2 option is given.
+scan and upgrade compare against the balanced preset unless any Argon2 option is given.
+With --recovery the password is the 24 words of the recovery key, separated by spaces.
+
+--shred overwrites the data once before removing it. On SSDs and copy on write filesystems (Btrfs, ZFS, APFS)
+or with snapshots and backups the original data may still be recoverable.";
+
+enum Command {
+   Encrypt,
+   Decrypt,
+   Inspect,
+   Keygen,
+   Slots,
+   AddPassword,
+   AddRecipient,
+   RemoveSlot,
+   ChangePassword,
+   Scan,
+   Upgrade,
+}
+
+enum PasswordSource {
+   Prompt,
+   Env(String),
+   Fd(i32),
+}
+
+struct Args {
+   command: Command,
+   file_path: PathBuf,
+   username: Option<String>,
+   password_source: PasswordSource,
+   argon2: Argon2,
+   /// Whether any Argon2 option was given
+   argon2_set: bool,
+   keyfiles: Vec<Keyfile>,
+   /// Parsed once the format is known
+   recipients: Vec<String>,
+   identity: Option<PathBuf>,
+   new_username: Option<String>,
+   new_password_env: Option<String>,
+   new_keyfiles: Vec<Keyfile>,
+   /// Numbered from 1
+   slot: Option<usize>,
+   /// The shares needed and the shares to print
+   shares: Option<(u8, u8)>,
+   share_inputs: Vec<String>,
+   recovery_key: bool,
+   recovery_sheet: Option<PathBuf>,
+   /// Decrypt with a recovery key read like the password
+   recovery: bool,
+   age: bool,
+   options: Options,
+}
+
+impl Args {
+   fn parse(mut args: impl Iterator<Item = String>) -> Result<Self, String> {
+      let command = match args.next().as_deref() {
+         Some("encrypt") => Command::Encrypt,
+         Some("decrypt") => Command::Decrypt,
+         Some("inspect") => Command::Inspect,
+         Some("keygen") => Command::Keygen,
+         Some("slots") => Command::Slots,
+         Some("add-password") => Command::AddPassword,
+         Some("add-recipient") => Command::AddRecipient,
+         Some("remove-slot") => Command::RemoveSlot,
+         Some("change-password") => Command::ChangePassword,
+         Some("scan") => Command::Scan,
+         Some("upgrade") => Command::Upgrade,
+         Some(other) => return Err(format!("Unknown command: {}", other)),
+         None => return Err("No command given".to_string()),
+      };
+
+      let mut file_path = None;
+      let mut username = None;
+      let mut password_source = PasswordSource::Prompt;
+      let mut argon2 = Argon2::balanced();
+      let mut argon2_set = false;
+      let mut keyfiles = Vec::new();
+      let mut recipients = Vec::new();
+      let mut identity = None;
+      let mut new_username = None;
+      let mut new_password_env = None;
+      let mut new_keyfiles = Vec::new();
+      let mut slot = None;
+      let mut shares = None;
+      let mut share_inputs = Vec::new();
+      let mut recovery_key = false;
+      let mut recovery_sheet = None;
+      let mut recovery = false;
+      let mut age = false;
+      let mut options = Options::default();
+
+      while let Some(arg) = args.next() {
+         argon2_set |= matches!(
+            arg.as_str(),
+            "--preset" | "--m-cost" | "--t-cost" | "--p-cost"
+         );
+         let mut value = || args.next().ok_or(format!("Missing value for {}", arg));
+
+         match arg.as_str() {
+            "--username" => username = Some(value()?),
+            "--password-env" => password_source = PasswordSource::Env(value()?),
+            "--password-fd" => {
+               let fd = value()?;
+               let fd = fd
+                  .parse()
+                  .map_err(|_| format!("Invalid file descriptor: {}", fd))?;
+               password_source = PasswordSource::Fd(fd);
+            }
+            "--preset" => {
+               let name = value()?;
+               argon2 = Preset::from_name(&name)
+                  .and_then(|preset| preset.argon2())
+                  .ok_or(format!("Unknown preset: {}", name))?;
+            }
+            "--m-cost" => argon2.m_cost = parse_number(&arg, value()?)?,
+            "--t-cost" => argon2.t_cost = parse_number(&arg, value()?)?,
+            "--p-cost" => argon2.p_cost = parse_number(&arg, value()?)?,
+            "--keyfile" => {
+               keyfiles.push(Keyfile::Path(PathBuf::from(value()?)));
+               options.keyfile = true;
+            }
+            "--recipient" => recipients.push(value()?),
+            "--identity" => identity = Some(PathBuf::from(value()?)),
+            "--new-username" => new_username = Some(value()?),
+            "--new-password-env" => new_password_env = Some(value()?),
+            "--new-keyfile" => new_keyfiles.push(Keyfile::Path(PathBuf::from(value()?))),
+            "--slot" => slot = Some(parse_number(&arg, value()?)?),
+            "--shares" => {
+               let value = value()?;
+               let (threshold, count) = value
+                  .split_once('/')
+                  .ok_or(format!("Invalid value for {}: {}", arg, value))?;
+               shares = Some((
+                  parse_number(&arg, threshold.to_string())?,
+                  parse_number(&arg, count.to_string())?,
+               ));
+            }
+            "--share" => share_inputs.push(value()?),
+            "--recovery-key" => recovery_key = true,
+            "--recovery-sheet" => {
+               recovery_sheet = Some(PathBuf::from(value()?));
+               recovery_key = true;
+            }
+            "--recovery" => recovery = true,
+            "--age" => age = true,
+            "--output" => options.output_path = Some(PathBuf::from(value()?)),
+            "--hide-name" => options.neutral_name = true,
+            "--verify" => options.verify = true,
+            "--shred" => options.shred = true,
+            "--ignore-memory-limit" => options.ignore_memory_limit = true,
+            "--overwrite" => options.overwrite = Overwrite::Replace,
+            "--keep-both" => options.overwrite = Overwrite::KeepBoth,
+            _ if arg.starts_with("--") => return Err(format!("Unknown option: {}", arg)),
+            _ if file_path.is_none() => file_path = Some(PathBuf::from(arg)),
+            _ => return Err(format!("Unexpected argument: {}", arg)),
+         }
+      }
+
+      let file_path = file_path.ok_or("No file given".to_string())?;
+
+      Ok(Self {
+         command,
+         file_path,
+         username,
+         password_source,
+         argon2,
+         argon2_set,
+         keyfiles,
+         recipients,
+         identity,
+         new_username,
+         new_password_env,
+         new_keyfiles,
+         slot,
+         shares,
+         share_inputs,
+         recovery_key,
+         recovery_sheet,
+         recovery,
+         age,
+         options,
+      })
+   }
+
+   fn credentials(&self, confirm: bool) -> Result<Credentials, String> {
+      let username = match &self.username {
+         Some(username) => username.clone(),
+         None => prompt_line("Username: ")?,
+      };
+
+      keyfile::credentials(
+         SecureString::from(username),
+         self.password(confirm)?,
+         &self.keyfiles,
+      )
+      .map_err(|e| e.to_string())
+   }
+
+   fn password(&self, confirm: bool) -> Result<SecureString, String> {
+      let mut password = match &self.password_source {
+         PasswordSource::Prompt => {
+            let mut password = prompt_password("Password: ")?;
+            if confirm {
+               let mut confirm_password = prompt_password("Confirm password: ")?;
+               let matches = password == confirm_password;
+               confirm_password.zeroize();
+               if !matches {
+                  password.zeroize();
+                  return Err("Passwords do not match".to_string());
+               }
+            }
+            password
+         }
+         PasswordSource::Env(var) => {
+            std::env::var(var).map_err(|e| format!("Error reading password from ${}: {}", var, e))?
+         }
+         PasswordSource::Fd(fd) => read_password_fd(*fd)?,
+      };
+
+      let password_secure = SecureString::from(password.clone());
+      password.zeroize();
+      Ok(password_secure)
+   }
+
+   /// The credentials given, or the identity they unlock with --identity
+   fn secret(&self) -> Result<Secret, String> {
+      let credentials = self.credentials(false)?;
+      match &self.identity {
+         Some(identity_path) => Identity::load(identity_path, credentials)
+            .map(Secret::from)
+            .map_err(|e| e.to_string()),
+         None => Ok(Secret::from(credentials)),
+      }
+   }
+
+   /// The new credentials of add-password and change-password, with `keyfiles` mixed in
+   fn new_credentials(&self, keyfiles: &[Keyfile]) -> Result<Credentials, String> {
+      let username = match &self.new_username {
+         Some(username) => username.clone(),
+         None => prompt_line("New username: ")?,
+      };
+
+      let mut password = match &self.new_password_env {
+         Some(var) => std::env::var(var).map_err(|e| format!("Error reading password from ${}: {}", var, e))?,
+         None => {
+            let mut password = prompt_password("New password: ")?;
+            let mut confirm_password = prompt_password("Confirm new password: ")?;
+            let matches = password == confirm_password;
+            confirm_password.zeroize();
+            if !matches {
+               password.zeroize();
+               return Err("Passwords do not match".to_string());
+            }
+            password
+         }
+      };
+
+      let password_secure = SecureString::from(password.clone());
+      password.zeroize();
+      keyfile::credentials(SecureString::from(username), password_secure, keyfiles).map_err(|e| e.to_string())
+   }
+
+   fn recipients(&self) -> Result<Vec<Recipient>, String> {
+      self
+         .recipients
+         .iter()
+         .map(|key| key.parse::<Recipient>().map_err(|e| e.to_string()))
+         .collect()
+   }
+}
+
+fn parse_number<T: std::str::FromStr>(arg: &str, value: String) -> Result<T, String> {
+   value
+      .parse()
+      .map_err(|_| format!("Invalid value for {}: {}", arg, value))
+}
+
+fn prompt_line(prompt: &str) -> Result<String, String> {
+   if !std::io::stdin().is_terminal() {
+      return Err("Cannot prompt for the username, stdin is not a terminal (use --username)".to_string());
+   }
+
+   eprint!("{}", prompt);
+   std::io::stderr().flush().ok();
+
+   let mut line = String::new();
+   std::io::stdin()
+      .read_line(&mut line)
+      .map_err(|e| format!("Error reading input: {}", e))?;
+
+   Ok(line.trim_end_matches(['\r', '\n']).to_string())
+}
+
+fn prompt_password(prompt: &str) -> Result<String, String> {
+   if !std::io::stdin().is_terminal() {
+      return Err(
+         "Cannot prompt for the password, stdin is not a terminal (use --password-env or --password-fd)".to_string(),
+      );
+   }
+
+   rpassword::prompt_password(prompt).map_err(|e| format!("Error reading password: {}", e))
+}
+
+#[cfg(unix)]
+fn read_password_fd(fd: i32) -> Result<String, String> {
+   use std::os::fd::FromRawFd;
+
+   // Safety: the caller explicitly handed us this descriptor, we own it from now on
+   let mut file = unsafe { std::fs::File::from_raw_fd(fd) };
+
+   let mut password = String::new();
+   file
+      .read_to_string(&mut password)
+      .map_err(|e| format!("Error reading password from fd {}: {}", fd, e))?;
+
+   // Only strip the trailing newline, anything else is part of the password
+   if password.ends_with('\n') {
+      password.pop();
+      if password.ends_with('\r') {
+         password.pop();
+      }
+   }
+
+   Ok(password)
+}
+
+#[cfg(not(unix))]
+fn read_password_fd(_fd: i32) -> Result<String, String> {
+   Err("--password-fd is only supported on Unix".to_string())
+}
+
+fn print_info(file_path: &Path) -> Result<(), String> {
+   let info = inspect(file_path).map_err(|e| e.to_string())?;
+
+   println!("File:          {}", file_path.display());
+   println!("{}", info);
+   if let Some(warning) = info.memory_warning() {
+      println!("\nWarning: {}", warning);
+   }
+
+   Ok(())
+}
+
+fn run(args: Args) -> Result<(), String> {
+   match args.command {
+      _ if args.age => run_age(args)?,
+      Command::Encrypt => {
+         let progress = Progress::new();
+         let mut key = if args.recipients.is_empty() {
+            derive_key(
+               args.argon2.clone(),
+               args.credentials(true)?,
+               &args.options,
+               &progress,
+            )
+         } else {
+            DataKey::for_recipients(&args.recipients()?)
+         }
+         .map_err(|e| e.to_string())?;
+
+         let shares = match args.shares {
+            Some((threshold, count)) => {
+               let recovery_key = RecoveryKey::generate();
+               key.add_recovery(&recovery_key).map_err(|e| e.to_string())?;
+               shamir::split(&recovery_key, threshold, count).map_err(|e| e.to_string())?
+            }
+            None => Vec::new(),
+         };
+
+         let recovery_key = args.recovery_key.then(RecoveryKey::generate);
+         if let Some(recovery_key) = &recovery_key {
+            key.add_recovery(recovery_key).map_err(|e| e.to_string())?;
+         }
+
+         let res = if args.file_path.is_dir() {
+            encrypt_dir_with_key(&key, &args.file_path, &args.options, &progress)
+         } else {
+            encrypt_file_with_key(&key, &args.file_path, &args.options, &progress)
+         };
+         let new_file_path = res.map_err(|e| e.to_string())?;
+         if args.recipients.is_empty() {
+            println!("File encrypted successfully to {}", new_file_path.display());
+         } else {
+            println!(
+               "File encrypted successfully to {} for {} recipients",
+               new_file_path.display(),
+               args.recipients.len()
+            );
+         }
+         if args.options.verifies() {
+            println!("Verified that it decrypts back to the original");
+         }
+         if args.options.shred {
+            println!("Removed the original {}", args.file_path.display());
+         }
+         if let Some(share) = shares.first() {
+            println!(
+               "\nRecovery shares, any {} of them decrypt the file. They are not shown again:",
+               share.threshold()
+            );
+            for share in &shares {
+               println!("{}", share);
+            }
+         }
+         if let Some(recovery_key) = &recovery_key {
+            println!(
+               "\nRecovery key, it opens the file without the password. It is not shown again:\n{}",
+               recovery_key.to_words().as_str()
+            );
+         }
+         if let (Some(recovery_key), Some(path)) = (&recovery_key, &args.recovery_sheet) {
+            let sheet = recovery_key.sheet().map_err(|e| e.to_string())?;
+            let path = write_file(path, args.options.overwrite, |writer| {
+               writer.write_all(sheet.as_bytes())?;
+               Ok(())
+            })
+            .map_err(|e| e.to_string())?;
+            println!("Recovery sheet saved to {}", path.display());
+         }
+      }
+      Command::Decrypt if args.recovery => {
+         let mut recovery_key = Err("No recovery key given".to_string());
+         args.password(false)?.unlock_str(|words| {
+            recovery_key = RecoveryKey::from_words(words).map_err(|e| e.to_string());
+         });
+         let new_file_path = decrypt_file(&args.file_path, recovery_key?, &args.options).map_err(|e| e.to_string())?;
+         println!("File decrypted successfully to {}", new_file_path.display());
+      }
+      Command::Decrypt if !args.share_inputs.is_empty() => {
+         let shares = args
+            .share_inputs
+            .iter()
+            .map(|share| share.parse::<Share>())
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| e.to_string())?;
+         let recovery_key = shamir::combine(&shares).map_err(|e| e.to_string())?;
+         let new_file_path = decrypt_file(&args.file_path, recovery_key, &args.options).map_err(|e| e.to_string())?;
+         println!("File decrypted successfully to {}", new_file_path.display());
+      }
+      Command::Decrypt => {
+         let credentials = args.credentials(false)?;
+         let res = match &args.identity {
+            Some(identity_path) => {
+               // The keyfiles belong to the identity, not to the file
+               let identity = Identity::load(identity_path, credentials).map_err(|e| e.to_string())?;
+               let options = Options {
+                  keyfile: false,
+                  ..args.options.clone()
+               };
+               decrypt_file(&args.file_path, identity, &options)
+            }
+            None => decrypt_file(&args.file_path, credentials, &args.options),
+         };
+         let new_file_path = res.map_err(|e| e.to_string())?;
+         println!("File decrypted successfully to {}", new_file_path.display());
+      }
+      Command::Inspect => print_info(&args.file_path)?,
+      Command::Keygen => {
+         let credentials = args.credentials(true)?;
+         let identity = Identity::generate();
+         let path = identity
+            .save(
+               &args.file_path,
+               args.argon2.clone(),
+               credentials,
+               args.options.overwrite,
+            )
+            .map_err(|e| e.to_string())?;
+         println!("Identity saved to {}", path.display());
+         println!("Public key: {}", identity.recipient());
+      }
+      Command::Slots => {
+         let slots = slots::list(&args.file_path).map_err(|e| e.to_string())?;
+         for (i, slot) in slots.iter().enumerate() {
+            println!("{}: {}", i + 1, slot);
+         }
+      }
+      Command::AddPassword => {
+         let secret = args.secret()?;
+         let credentials = args.new_credentials(&args.keyfiles)?;
+         slots::add_password(
+            &args.file_path,
+            &secret,
+            args.argon2.clone(),
+            credentials,
+            &args.options,
+            &Progress::new(),
+         )
+         .map_err(|e| e.to_string())?;
+         println!("Password added to {}", args.file_path.display());
+      }
+      Command::AddRecipient => {
+         let [recipient] = args.recipients()?[..] else {
+            return Err("Give exactly one --recipient to add".to_string());
+         };
+         let secret = args.secret()?;
+         slots::add_recipient(
+            &args.file_path,
+            &secret,
+            &recipient,
+            &args.options,
+            &Progress::new(),
+         )
+         .map_err(|e| e.to_string())?;
+         println!("Recipient added to {}", args.file_path.display());
+      }
+      Command::RemoveSlot => {
+         let index = match args.slot {
+            Some(slot) if slot > 0 => slot - 1,
+            _ => return Err("Give the slot to remove with --slot, see the slots command".to_string()),
+         };
+         let secret = args.secret()?;
+         slots::remove(
+            &args.file_path,
+            index,
+            &secret,
+            &args.options,
+            &Progress::new(),
+         )
+         .map_err(|e| e.to_string())?;
+         println!(
+            "Slot {} removed from {}",
+            index + 1,
+            args.file_path.display()
+         );
+      }
+      Command::ChangePassword => {
+         let old = args.credentials(false)?;
+         let new = args.new_credentials(&args.new_keyfiles)?;
+         rekey::change_credentials(
+            &args.file_path,
+            old,
+            args.argon2_set.then(|| args.argon2.clone()),
+            new,
+            !args.new_keyfiles.is_empty(),
+            &args.options,
+            &Progress::new(),
+         )
+         .map_err(|e| e.to_string())?;
+         println!("Password of {} changed", args.file_path.display());
+      }
+      Command::Scan => {
+         let report = rekey::scan(&args.file_path, &args.argon2).map_err(|e| e.to_string())?;
+         println!("{}", report.message(&args.argon2));
+      }
+      Command::Upgrade => {
+         let report = rekey::scan(&args.file_path, &args.argon2).map_err(|e| e.to_string())?;
+         println!("{}", report.message(&args.argon2));
+         if report.weak.is_empty() {
+            return Ok(());
+         }
+
+         let paths: Vec<PathBuf> = report.weak.into_iter().map(|file| file.path).collect();
+         let credentials = args.credentials(false)?;
+         let summary = rekey_batch(
+            args.argon2.clone(),
+            &paths,
+            credentials,
+            &args.options,
+            &Progress::new(),
+            |_, _| {},
+         );
+         println!("\n{}", summary.message("re-keyed"));
+         if !summary.failed.is_empty() {
+            return Err(format!(
+               "{} files could not be re-keyed",
+               summary.failed.len()
+            ));
+         }
+      }
+   }
+
+   Ok(())
+}
+
+/// Encrypts or decrypts in the age format, with a passphrase or X25519 keys
+fn run_age(args: Args) -> Result<(), String> {
+   if !args.keyfiles.is_empty() {
+      return Err("age files can't use keyfiles".to_string());
+   }
+
+   if args.shares.is_some() || !args.share_inputs.is_empty() || args.recovery_key || args.recovery {
+      return Err("age files can't have a recovery key".to_string());
+   }
+
+   let progress = Progress::new();
+
+   match args.command {
+      Command::Encrypt => {
+         let target = if args.recipients.is_empty() {
+            AgeTarget::Passphrase(args.password(true)?)
+         } else {
+            let recipients = args
+               .recipients
+               .iter()
+               .map(|key| {
+                  key.parse::<age::x25519::Recipient>()
+                     .map_err(|e| format!("Not a valid age public key {}: {}", key, e))
+               })
+               .collect::<Result<_, _>>()?;
+            AgeTarget::Recipients(recipients)
+         };
+
+         let new_file_path =
+            age_format::encrypt_file(&target, &args.file_path, &args.options, &progress).map_err(|e| e.to_string())?;
+         println!("File encrypted successfully to {}", new_file_path.display());
+      }
+      Command::Decrypt => {
+         let secret = match &args.identity {
+            Some(path) => AgeSecret::Identities(age_format::read_identities(path).map_err(|e| e.to_string())?),
+            None => AgeSecret::Passphrase(args.password(false)?),
+         };
+
+         let new_file_path =
+            age_format::decrypt_file(&secret, &args.file_path, &args.options, &progress).map_err(|e| e.to_string())?;
+         println!("File decrypted successfully to {}", new_file_path.display());
+      }
+      Command::Inspect => print_info(&args.file_path)?,
+      Command::Keygen => return Err("Use age-keygen to generate age identities".to_string()),
+      _ => return Err("age files have no key slots".to_string()),
+   }
+
+   Ok(())
+}
+
+fn main() -> ExitCode {
+   let args: Vec<String> = std::env::args().skip(1).collect();
+
+   if args.is_empty() || args.iter().any(|arg| arg == "-h" || arg == "--help") {
+      println!("{}", USAGE);
+      return ExitCode::SUCCESS;
+   }
+
+   let args = match Args::parse(args.into_iter()) {
+      Ok(args) => args,
+      Err(e) => {
+         eprintln!("{}\n\n{}", e, USAGE);
+         return ExitCode::FAILURE;
+      }
+   };
+
+   match run(args) {
+      Ok(()) => ExitCode::SUCCESS,
+      Err(e) => {
+         eprintln!("{}", e);
+         ExitCode::FAILURE
+      }
+   }
+}
@@ -0,0 +1,272 @@
+//! Reading and writing files in the [age](https://age-encryption.org) format, so files can be exchanged with
+//! `age` and `rage` users
+//!
+//! age files hold the contents of a single file and nothing else, so directories, the original
+//! [Metadata](super::Metadata), verification and shredding stay with the nCrypt format. Both passphrases (scrypt)
+//! and X25519 recipients (`age1...` keys) are supported, armored files can be decrypted as well.
+
+use super::{
+   Error, Options,
+   output::write_file,
+   progress::{Phase, Progress, ProgressReader, ProgressWriter},
+   strip_extension,
+};
+use age::{armor::ArmoredReader, secrecy::SecretString, x25519};
+use ncrypt_me::secure_types::SecureString;
+use std::fs::File;
+use std::io::{BufRead, BufReader, Read};
+use std::path::{Path, PathBuf};
+use zeroize::Zeroize;
+
+/// The extension appended to age files
+pub const AGE_EXTENSION: &str = ".age";
+
+const MAGIC: &[u8] = b"age-encryption.org/v1\n";
+const ARMOR_MAGIC: &[u8] = b"-----BEGIN AGE ENCRYPTED FILE-----";
+const IDENTITY_PREFIX: &str = "AGE-SECRET-KEY-";
+
+/// Identity files are a few lines of text, anything bigger is something else
+const MAX_IDENTITY_FILE_SIZE: u64 = 64 * 1024;
+const MAX_HEADER_SIZE: usize = 1024 * 1024;
+
+/// Who can decrypt an age file
+#[derive(Clone)]
+pub enum AgeTarget {
+   Passphrase(SecureString),
+   Recipients(Vec<x25519::Recipient>),
+}
+
+/// What opens an age file
+#[derive(Clone)]
+pub enum AgeSecret {
+   Passphrase(SecureString),
+   Identities(Vec<x25519::Identity>),
+}
+
+/// What the header of an age file says, no secrets needed
+#[derive(Clone, Copy, Debug)]
+pub struct AgeHeader {
+   /// Whether the file is encrypted with a passphrase
+   pub passphrase: bool,
+   /// The number of recipient stanzas
+   pub recipients: usize,
+   /// The size of the header in bytes, after removing any armor
+   pub size: usize,
+}
+
+/// The path an age copy of `file_path` is written to
+pub fn encrypted_path(file_path: &Path) -> PathBuf {
+   let mut path = file_path.as_os_str().to_owned();
+   path.push(AGE_EXTENSION);
+   PathBuf::from(path)
+}
+
+/// The path a decrypted copy of `file_path` is written to, age files don't store the original name
+pub fn decrypted_path(file_path: &Path) -> PathBuf {
+   strip_extension(file_path, AGE_EXTENSION)
+}
+
+/// Whether the file at `path` starts like an age file, armored or not
+pub fn is_age_file(path: &Path) -> Result<bool, Error> {
+   let file = File::open(path).map_err(|e| Error::read(path, e))?;
+   let mut start = Vec::with_capacity(ARMOR_MAGIC.len());
+   file
+      .take(ARMOR_MAGIC.len() as u64)
+      .read_to_end(&mut start)
+      .map_err(|e| Error::read(path, e))?;
+
+   Ok(start.starts_with(MAGIC) || start.starts_with(ARMOR_MAGIC))
+}
+
+/// Encrypts the file at `file_path` to [encrypted_path], unless the [Options] say otherwise
+///
+/// Returns the path of the encrypted file
+pub fn encrypt_file(
+   target: &AgeTarget,
+   file_path: &Path,
+   options: &Options,
+   progress: &Progress,
+) -> Result<PathBuf, Error> {
+   if file_path.is_dir() {
+      return Err(Error::Encrypt(
+         "age files hold a single file, use the nCrypt format for folders".to_string(),
+      ));
+   }
+
+   if options.verifies() {
+      return Err(Error::Encrypt(
+         "Verifying and shredding are only supported in the nCrypt format".to_string(),
+      ));
+   }
+
+   let encryptor = match target {
+      AgeTarget::Passphrase(passphrase) => age::Encryptor::with_user_passphrase(
+         secret_string(passphrase).ok_or(Error::Encrypt("Enter a passphrase".to_string()))?,
+      ),
+      AgeTarget::Recipients(recipients) => {
+         age::Encryptor::with_recipients(recipients.iter().map(|r| r as &dyn age::Recipient))
+            .map_err(|e| Error::Encrypt(e.to_string()))?
+      }
+   };
+
+   let file = File::open(file_path).map_err(|e| Error::read(file_path, e))?;
+   let total = file.metadata().map(|m| m.len()).unwrap_or(0);
+   let mut reader = BufReader::new(file);
+
+   let new_file_path = options.output_for(encrypted_path(file_path));
+   write_file(&new_file_path, options.overwrite, |writer| {
+      // A passphrase runs scrypt right here
+      progress.set_phase(Phase::DerivingKey);
+      let writer = encryptor.wrap_output(writer)?;
+
+      progress.start(Phase::Encrypting, total);
+      let mut writer = ProgressWriter::new(writer, progress);
+      std::io::copy(&mut reader, &mut writer)?;
+
+      progress.set_phase(Phase::Writing);
+      writer.into_inner().finish()?;
+      Ok(())
+   })
+}
+
+/// Decrypts the age file at `file_path` to [decrypted_path], unless the [Options] say otherwise
+///
+/// Returns the path of the decrypted file
+pub fn decrypt_file(
+   secret: &AgeSecret,
+   file_path: &Path,
+   options: &Options,
+   progress: &Progress,
+) -> Result<PathBuf, Error> {
+   if !is_age_file(file_path)? {
+      return Err(Error::InvalidFormat(
+         "Not an age file, turn off the age format to decrypt it".to_string(),
+      ));
+   }
+
+   let file = File::open(file_path).map_err(|e| Error::read(file_path, e))?;
+   let total = file.metadata().map(|m| m.len()).unwrap_or(0);
+
+   // Count the ciphertext from the start, the header is read along with the key
+   progress.start(Phase::DerivingKey, total);
+   let reader = BufReader::new(ProgressReader::new(file, progress));
+   let decryptor = age::Decryptor::new(ArmoredReader::new(reader)).map_err(|e| Error::Decrypt(e.to_string()))?;
+   let res = match secret {
+      AgeSecret::Passphrase(passphrase) => {
+         if !decryptor.is_scrypt() {
+            return Err(Error::Decrypt(
+               "This age file was encrypted to recipients, load an identity to decrypt it".to_string(),
+            ));
+         }
+         let identity = age::scrypt::Identity::new(
+            secret_string(passphrase).ok_or(Error::Decrypt("Enter the passphrase".to_string()))?,
+         );
+         decryptor.decrypt(std::iter::once(&identity as &dyn age::Identity))
+      }
+      AgeSecret::Identities(identities) => {
+         if decryptor.is_scrypt() {
+            return Err(Error::Decrypt(
+               "This age file was encrypted with a passphrase, turn off public keys to decrypt it".to_string(),
+            ));
+         }
+         decryptor.decrypt(identities.iter().map(|i| i as &dyn age::Identity))
+      }
+   };
+   let mut reader = res.map_err(|e| Error::Decrypt(e.to_string()))?;
+   progress.check()?;
+
+   let new_file_path = options.output_for(decrypted_path(file_path));
+
+   progress.set_phase(Phase::Decrypting);
+   write_file(&new_file_path, options.overwrite, |writer| {
+      std::io::copy(&mut reader, writer)?;
+      progress.set_phase(Phase::Writing);
+      Ok(())
+   })
+}
+
+/// Parses the header of the age file at `file_path`
+pub fn read_header(file_path: &Path) -> Result<AgeHeader, Error> {
+   let file = File::open(file_path).map_err(|e| Error::read(file_path, e))?;
+   let mut reader = BufReader::new(ArmoredReader::new(BufReader::new(file)));
+
+   let mut header = AgeHeader {
+      passphrase: false,
+      recipients: 0,
+      size: 0,
+   };
+
+   let mut line = String::new();
+   loop {
+      line.clear();
+      let read = reader.read_line(&mut line)?;
+      if read == 0 || header.size > MAX_HEADER_SIZE {
+         return Err(Error::InvalidFormat("Truncated age header".to_string()));
+      }
+      header.size += read;
+
+      if line.starts_with("---") {
+         return Ok(header);
+      }
+
+      if let Some(stanza) = line.strip_prefix("-> ") {
+         match stanza.split_whitespace().next() {
+            Some("scrypt") => header.passphrase = true,
+            // age adds stanzas like these at random so parsers keep accepting unknown ones
+            Some(tag) if tag.ends_with("-grease") => {}
+            Some(_) => header.recipients += 1,
+            None => {}
+         }
+      }
+   }
+}
+
+/// Whether the file at `path` is a plain text age identity file as written by `age-keygen`
+pub fn is_identity_file(path: &Path) -> bool {
+   let Ok(file) = File::open(path) else {
+      return false;
+   };
+
+   let mut text = String::new();
+   let res = file.take(MAX_IDENTITY_FILE_SIZE).read_to_string(&mut text);
+   let found = res.is_ok()
+      && text
+         .lines()
+         .any(|line| line.trim().starts_with(IDENTITY_PREFIX));
+   text.zeroize();
+   found
+}
+
+/// Reads every `AGE-SECRET-KEY-...` line of the identity file at `path`
+pub fn read_identities(path: &Path) -> Result<Vec<x25519::Identity>, Error> {
+   let mut file = File::open(path)
+      .map_err(|e| Error::read(path, e))?
+      .take(MAX_IDENTITY_FILE_SIZE);
+   let mut text = String::new();
+   file
+      .read_to_string(&mut text)
+      .map_err(|e| Error::read(path, e))?;
+
+   let identities: Result<Vec<x25519::Identity>, _> = text
+      .lines()
+      .map(str::trim)
+      .filter(|line| !line.is_empty() && !line.starts_with('#'))
+      .map(|line| line.parse::<x25519::Identity>())
+      .collect();
+   text.zeroize();
+
+   match identities {
+      Ok(identities) if !identities.is_empty() => Ok(identities),
+      _ => Err(Error::InvalidFormat(format!(
+         "{} is not an age identity file",
+         path.display()
+      ))),
+   }
+}
+
+/// The passphrase in the form age takes it, `None` if it is empty
+fn secret_string(passphrase: &SecureString) -> Option<SecretString> {
+   let mut text = String::new();
+   passphrase.unlock_str(|passphrase| text.push_str(passphrase));
+   (!text.is_empty()).then(|| SecretString::from(text))
+}
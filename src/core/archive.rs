@@ -0,0 +1,155 @@
+//! Encryption of whole directories
+//!
+//! The directory is packed into a tar archive on the fly, so the relative paths, permissions and modification
+//! times survive, and the archive is encrypted as the payload of a single [chunked](super::stream) file.
+
+use super::{
+   Error, Metadata, Options, PayloadKind,
+   output::{Overwrite, write_dir, write_file},
+   progress::{Phase, Progress, ProgressWriter},
+   stream::{DataKey, Encryptor},
+   verify::digest,
+};
+use ncrypt_me::{Argon2, Credentials};
+use std::fs::File;
+use std::io::{BufReader, Read, Write};
+use std::path::{Path, PathBuf};
+use tar::{Archive, Builder, HeaderMode};
+
+/// Encrypts the directory at `dir_path` and everything in it, writing the result to
+/// [encrypted_path](super::encrypted_path) unless the [Options] say otherwise
+///
+/// Symlinks are stored as links and not followed.
+///
+/// Returns the path of the encrypted file
+pub fn encrypt_dir(
+   argon2: Argon2,
+   dir_path: &Path,
+   credentials: Credentials,
+   options: &Options,
+) -> Result<PathBuf, Error> {
+   let progress = Progress::new();
+   let key = super::derive_key(argon2, credentials, options, &progress)?;
+   encrypt_dir_with_key(&key, dir_path, options, &progress)
+}
+
+/// Like [encrypt_dir] but with an already derived [DataKey], reporting to `progress`
+pub fn encrypt_dir_with_key(
+   key: &DataKey,
+   dir_path: &Path,
+   options: &Options,
+   progress: &Progress,
+) -> Result<PathBuf, Error> {
+   if !dir_path.is_dir() {
+      return Err(Error::read(
+         dir_path,
+         std::io::Error::new(std::io::ErrorKind::NotADirectory, "Not a directory"),
+      ));
+   }
+
+   if options.shred {
+      super::shred::check_original(dir_path)?;
+   }
+
+   let metadata = Metadata::from_path(dir_path)?;
+
+   let new_file_path = options.encrypted_output(dir_path);
+   let new_file_path = write_file(&new_file_path, options.overwrite, |writer| {
+      let mut encryptor = Encryptor::new(key, writer)?;
+      encryptor.write_all(&[PayloadKind::Directory as u8])?;
+      metadata.write_to(&mut encryptor)?;
+
+      // The tar headers are counted too, so this slightly overshoots
+      progress.start(Phase::Encrypting, dir_totals(dir_path).1);
+
+      let mut builder = Builder::new(ProgressWriter::new(encryptor, progress));
+      builder.mode(HeaderMode::Complete);
+      builder.follow_symlinks(false);
+      builder
+         .append_dir_all(".", dir_path)
+         .map_err(|e| match Error::from(e) {
+            Error::Io(e) => Error::read(dir_path, e),
+            e => e,
+         })?;
+
+      progress.set_phase(Phase::Writing);
+      builder.into_inner()?.into_inner().finish()?;
+      Ok(())
+   })?;
+
+   super::finish_encryption(key, dir_path, &new_file_path, options, progress)?;
+   Ok(new_file_path)
+}
+
+/// The number and total size of the regular files under `dir_path`, symlinks are not followed
+pub fn dir_totals(dir_path: &Path) -> (u64, u64) {
+   let Ok(entries) = std::fs::read_dir(dir_path) else {
+      return (0, 0);
+   };
+
+   entries
+      .filter_map(|entry| entry.ok())
+      .map(|entry| match entry.metadata() {
+         Ok(metadata) if metadata.is_dir() => dir_totals(&entry.path()),
+         Ok(metadata) if metadata.is_file() => (1, metadata.len()),
+         _ => (0, 0),
+      })
+      .fold((0, 0), |(files, size), (f, s)| (files + f, size + s))
+}
+
+/// Unpacks a decrypted archive into a directory at `dir_path` (or wherever `overwrite` says)
+///
+/// The archive is unpacked into a temporary directory first which is moved into place once complete.
+///
+/// Returns the path of the directory
+pub fn unpack<R: Read>(reader: R, dir_path: &Path, overwrite: Overwrite) -> Result<PathBuf, Error> {
+   write_dir(dir_path, overwrite, |temp_dir| {
+      let mut archive = Archive::new(reader);
+      archive.set_preserve_permissions(true);
+      archive.set_preserve_mtime(true);
+      archive.set_overwrite(false);
+      archive.unpack(temp_dir)?;
+
+      // Drain whatever follows the archive so the final chunk is authenticated too
+      std::io::copy(&mut archive.into_inner(), &mut std::io::sink())?;
+      Ok(())
+   })
+}
+
+/// Compares every file in a decrypted archive against the directory at `dir_path`
+pub fn verify<R: Read>(reader: R, dir_path: &Path) -> Result<(), Error> {
+   let mut archive = Archive::new(reader);
+   let mut files = 0;
+
+   for entry in archive.entries()? {
+      let mut entry = entry?;
+      if !entry.header().entry_type().is_file() {
+         continue;
+      }
+
+      let path = dir_path.join(entry.path()?);
+      let file = File::open(&path).map_err(|e| Error::read(&path, e))?;
+      let expected = digest(BufReader::new(file)).map_err(|e| match e {
+         Error::Io(e) => Error::read(&path, e),
+         e => e,
+      })?;
+
+      if digest(&mut entry)? != expected {
+         return Err(Error::Verify(format!(
+            "The encrypted copy of {} does not match the original",
+            path.display()
+         )));
+      }
+      files += 1;
+   }
+
+   std::io::copy(&mut archive.into_inner(), &mut std::io::sink())?;
+
+   if files != dir_totals(dir_path).0 {
+      return Err(Error::Verify(format!(
+         "The encrypted copy of {} is missing files",
+         dir_path.display()
+      )));
+   }
+   Ok(())
+}
@@ -0,0 +1,268 @@
+//! Encryption and decryption of many files with a single credential entry
+//!
+//! Encrypting a batch derives one [DataKey](super::stream::DataKey) up front, so Argon2 runs once no matter how many files are queued.
+//! Decrypting a batch made that way likewise unwraps the key once thanks to the [KeyCache].
+
+use super::{
+   Error, Options,
+   age_format::{self, AgeTarget},
+   decrypt_file_with_cache, derive_key, encrypt_dir_with_key, encrypt_file_with_key, memory,
+   progress::{Phase, Progress},
+   rekey,
+   stream::{DataKey, KeyCache, Secret},
+};
+use ncrypt_me::{Argon2, Credentials};
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+/// The state of a single file in a batch
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum JobStatus {
+   Queued,
+   Running,
+   /// Finished, holds the path of the output
+   Done(PathBuf),
+   Failed(String),
+}
+
+impl JobStatus {
+   pub fn is_finished(&self) -> bool {
+      matches!(self, Self::Done(_) | Self::Failed(_))
+   }
+}
+
+impl fmt::Display for JobStatus {
+   fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+      match self {
+         Self::Queued => write!(f, "Queued"),
+         Self::Running => write!(f, "Working..."),
+         Self::Done(_) => write!(f, "Done"),
+         Self::Failed(_) => write!(f, "Failed"),
+      }
+   }
+}
+
+/// The outcome of a whole batch
+#[derive(Clone, Debug, Default)]
+pub struct BatchSummary {
+   /// The output paths of the files that succeeded
+   pub succeeded: Vec<PathBuf>,
+   /// The input paths of the files that failed along with the reason
+   pub failed: Vec<(PathBuf, String)>,
+   /// The input paths of the files that failed with [Error::AlreadyExists] along with the output that exists
+   pub existing: Vec<(PathBuf, PathBuf)>,
+   /// The output paths of the files that succeeded only in part, along with what is left to do
+   pub partial: Vec<(PathBuf, String)>,
+   /// Whether the batch was cancelled before every file was processed
+   pub cancelled: bool,
+   /// Whether the files that succeeded were [verified](super::verify) as well
+   pub verified: bool,
+   /// The originals that were [shredded](super::shred) after encryption
+   pub shredded: Vec<PathBuf>,
+}
+
+impl BatchSummary {
+   /// A message describing the outcome, `action` is what was done e.g. "encrypted"
+   pub fn message(&self, action: &str) -> String {
+      let mut msg = if self.failed.is_empty() && self.succeeded.len() == 1 {
+         format!(
+            "File {} successfully to {}",
+            action,
+            self.succeeded[0].display()
+         )
+      } else {
+         let total = self.succeeded.len() + self.failed.len();
+         format!(
+            "{} of {} files {} successfully",
+            self.succeeded.len(),
+            total,
+            action
+         )
+      };
+
+      for (path, error) in &self.failed {
+         let name = path
+            .file_name()
+            .unwrap_or(path.as_os_str())
+            .to_string_lossy();
+         msg.push_str(&format!("\n\n{}: {}", name, error));
+      }
+
+      for (path, warning) in &self.partial {
+         let name = path
+            .file_name()
+            .unwrap_or(path.as_os_str())
+            .to_string_lossy();
+         msg.push_str(&format!("\n\n{}: {}", name, warning));
+      }
+
+      if self.verified && !self.succeeded.is_empty() {
+         msg.push_str("\n\nVerified: the encrypted files decrypt back to the originals");
+      }
+
+      if !self.shredded.is_empty() {
+         msg.push_str("\n\nRemoved the originals:");
+         for path in &self.shredded {
+            msg.push_str(&format!("\n{}", path.display()));
+         }
+      }
+
+      msg
+   }
+}
+
+/// Encrypts every file or directory in `paths` with the same credentials
+///
+/// `on_status` is called with the index into `paths` whenever a file changes state.
+/// An error is only returned if deriving the key fails or the batch is cancelled meanwhile,
+/// failures of single files end up in the [BatchSummary].
+pub fn encrypt_batch(
+   argon2: Argon2,
+   paths: &[PathBuf],
+   credentials: Credentials,
+   options: &Options,
+   progress: &Progress,
+   on_status: impl FnMut(usize, JobStatus),
+) -> Result<BatchSummary, Error> {
+   let key = derive_key(argon2, credentials, options, progress)?;
+   Ok(encrypt_batch_with_key(
+      &key, paths, options, progress, on_status,
+   ))
+}
+
+/// Like [encrypt_batch] but with an already generated [DataKey], e.g. one for [recipients](super::recipient)
+pub fn encrypt_batch_with_key(
+   key: &DataKey,
+   paths: &[PathBuf],
+   options: &Options,
+   progress: &Progress,
+   on_status: impl FnMut(usize, JobStatus),
+) -> BatchSummary {
+   let mut shredded = Vec::new();
+   let mut summary = run(paths, progress, on_status, |path| {
+      let output = if path.is_dir() {
+         encrypt_dir_with_key(key, path, options, progress)?
+      } else {
+         encrypt_file_with_key(key, path, options, progress)?
+      };
+
+      if options.shred {
+         shredded.push(path.to_path_buf());
+      }
+      Ok(output)
+   });
+
+   summary.verified = options.verifies();
+   summary.shredded = shredded;
+   summary
+}
+
+/// Encrypts every file in `paths` to the [age format](super::age_format), otherwise like [encrypt_batch]
+pub fn encrypt_batch_age(
+   target: &AgeTarget,
+   paths: &[PathBuf],
+   options: &Options,
+   progress: &Progress,
+   on_status: impl FnMut(usize, JobStatus),
+) -> BatchSummary {
+   run(paths, progress, on_status, |path| {
+      age_format::encrypt_file(target, path, options, progress)
+   })
+}
+
+/// Decrypts every file in `paths` with the same credentials or identity
+///
+/// `on_status` is called with the index into `paths` whenever a file changes state.
+pub fn decrypt_batch(
+   paths: &[PathBuf],
+   secret: impl Into<Secret>,
+   options: &Options,
+   progress: &Progress,
+   on_status: impl FnMut(usize, JobStatus),
+) -> BatchSummary {
+   let secret = secret.into();
+   let mut cache = KeyCache::default();
+   run(paths, progress, on_status, |path| {
+      decrypt_file_with_cache(path, &secret, &mut cache, options, progress)
+   })
+}
+
+/// Re-keys every file in `paths` with `argon2`, they keep opening with the same credentials
+///
+/// Only the password slot `credentials` open is re-keyed, see [rekey::change_credentials]. Files with another
+/// password still below `argon2` succeed in part and end up in [BatchSummary::partial].
+pub fn rekey_batch(
+   argon2: Argon2,
+   paths: &[PathBuf],
+   credentials: Credentials,
+   options: &Options,
+   progress: &Progress,
+   on_status: impl FnMut(usize, JobStatus),
+) -> BatchSummary {
+   let mut partial = Vec::new();
+   let mut summary = run(paths, progress, on_status, |path| {
+      rekey::change_credentials(
+         path,
+         credentials.clone(),
+         Some(argon2.clone()),
+         credentials.clone(),
+         options.keyfile,
+         options,
+         progress,
+      )?;
+
+      // The file is re-keyed already, a weak password left over doesn't undo that
+      if let Ok(Some(weakest)) = rekey::weakest_password(path)
+         && rekey::is_below(&weakest, &argon2)
+      {
+         partial.push((
+            path.to_path_buf(),
+            format!(
+               "Re-keyed, but another password is still at {} and {} iterations, re-key it with its own credentials",
+               memory::format_kib(weakest.m_cost as u64),
+               weakest.t_cost
+            ),
+         ));
+      }
+      Ok(path.to_path_buf())
+   });
+
+   summary.partial = partial;
+   summary
+}
+
+/// Runs `job` on every path until done or cancelled, the files not reached stay [JobStatus::Queued]
+fn run(
+   paths: &[PathBuf],
+   progress: &Progress,
+   mut on_status: impl FnMut(usize, JobStatus),
+   mut job: impl FnMut(&Path) -> Result<PathBuf, Error>,
+) -> BatchSummary {
+   let mut summary = BatchSummary::default();
+
+   for (i, path) in paths.iter().enumerate() {
+      if progress.is_cancelled() {
+         break;
+      }
+
+      on_status(i, JobStatus::Running);
+
+      match job(path) {
+         Ok(output) => {
+            summary.succeeded.push(output.clone());
+            on_status(i, JobStatus::Done(output));
+         }
+         Err(e) => {
+            if let Error::AlreadyExists(output) = &e {
+               summary.existing.push((path.clone(), output.clone()));
+            }
+            summary.failed.push((path.clone(), e.to_string()));
+            on_status(i, JobStatus::Failed(e.to_string()));
+         }
+      }
+   }
+
+   progress.set_phase(Phase::Idle);
+   summary.cancelled = progress.is_cancelled();
+   summary
+}
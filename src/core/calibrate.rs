@@ -0,0 +1,98 @@
+//! Picking Argon2 parameters that take a given time on this machine
+//!
+//! The running time of Argon2 grows about linearly with both the memory and the iterations. Memory matters most
+//! against attackers, so the whole budget is used unless even the fewest iterations are too slow, and the
+//! iterations are then scaled to the target. The final parameters are measured once more to report the real time.
+
+use super::{
+   Error, encrypt_bytes, memory,
+   progress::{Phase, Progress},
+};
+use ncrypt_me::{Argon2, Credentials, secure_types::SecureString};
+use std::time::{Duration, Instant};
+
+/// The smallest memory cost calibration goes down to, in KiB, also the lower end of the memory slider
+pub const MIN_M_COST: u32 = 65_536;
+/// The fewest iterations calibration goes down to, the lower end of the iterations slider
+///
+/// A handful of passes over the memory keeps time-memory tradeoff attacks on Argon2 expensive, so a slow machine
+/// gets less memory first rather than fewer iterations.
+pub const MIN_T_COST: u32 = 5;
+/// The most iterations calibration goes up to, the upper end of the iterations slider
+pub const MAX_T_COST: u32 = 1024;
+
+/// Parameters found by [calibrate] along with how long they took
+#[derive(Clone, Debug)]
+pub struct Calibration {
+   pub argon2: Argon2,
+   pub measured: Duration,
+}
+
+impl Calibration {
+   /// Roughly how long `argon2` takes on the machine this was measured on
+   pub fn estimate(&self, argon2: &Argon2) -> Duration {
+      let work = |argon2: &Argon2| argon2.m_cost as f64 * argon2.t_cost as f64;
+      self
+         .measured
+         .mul_f64(work(argon2) / work(&self.argon2).max(1.0))
+   }
+}
+
+/// A quick measurement with the smallest parameters, enough for [Calibration::estimate]
+pub fn reference() -> Result<Calibration, Error> {
+   let argon2 = params(MIN_M_COST, MIN_T_COST);
+   let measured = measure(argon2.clone())?;
+   Ok(Calibration { argon2, measured })
+}
+
+/// Measures how long encrypting with `argon2` takes, which is all key derivation
+///
+/// Fails with [Error::NotEnoughMemory] instead if `argon2` needs more memory than is available.
+pub fn measure(argon2: Argon2) -> Result<Duration, Error> {
+   memory::check(&argon2)?;
+
+   let secret = SecureString::from("calibration".to_string());
+   let credentials = Credentials::new(secret.clone(), secret.clone(), secret);
+
+   let start = Instant::now();
+   encrypt_bytes(argon2, &[0u8; 32], credentials)?;
+   Ok(start.elapsed())
+}
+
+/// Finds parameters that take about `target` on this machine using at most `memory_budget` KiB
+///
+/// This runs Argon2 several times, so it takes a few times `target`. A cancel takes effect between runs.
+pub fn calibrate(target: Duration, memory_budget: u32, progress: &Progress) -> Result<Calibration, Error> {
+   let mut argon2 = params(memory_budget.max(MIN_M_COST), MIN_T_COST);
+
+   progress.set_phase(Phase::Calibrating);
+   let mut measured = measure(argon2.clone())?;
+   progress.check()?;
+
+   // Too slow even with the fewest iterations, give up memory
+   while measured > target && argon2.m_cost / 2 >= MIN_M_COST {
+      argon2.m_cost /= 2;
+      measured = measure(argon2.clone())?;
+      progress.check()?;
+   }
+
+   if measured < target {
+      let scale = target.as_secs_f64() / measured.as_secs_f64().max(0.001);
+      let t_cost = ((argon2.t_cost as f64 * scale) as u32).clamp(MIN_T_COST, MAX_T_COST);
+
+      if t_cost != argon2.t_cost {
+         argon2.t_cost = t_cost;
+         measured = measure(argon2.clone())?;
+      }
+   }
+
+   Ok(Calibration { argon2, measured })
+}
+
+fn params(m_cost: u32, t_cost: u32) -> Argon2 {
+   let mut argon2 = Argon2::balanced();
+   argon2.m_cost = m_cost;
+   argon2.t_cost = t_cost;
+   argon2.p_cost = 1;
+   argon2
+}
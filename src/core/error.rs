@@ -0,0 +1,89 @@
+use std::path::PathBuf;
+use thiserror::Error;
+
+/// Errors returned by the [core](crate::core) operations
+#[derive(Debug, Error)]
+pub enum Error {
+   #[error("Error reading file {path}: {source}")]
+   Read {
+      path: PathBuf,
+      source: std::io::Error,
+   },
+
+   #[error("Error writing file {path}: {source}")]
+   Write {
+      path: PathBuf,
+      source: std::io::Error,
+   },
+
+   #[error("I/O error: {0}")]
+   Io(std::io::Error),
+
+   #[error("Invalid file format: {0}")]
+   InvalidFormat(String),
+
+   #[error("Error encrypting file: {0}")]
+   Encrypt(String),
+
+   #[error("Error decrypting file: {0}")]
+   Decrypt(String),
+
+   #[error("Error removing {path}: {source}")]
+   Shred {
+      path: PathBuf,
+      source: std::io::Error,
+   },
+
+   #[error("Verification failed: {0}")]
+   Verify(String),
+
+   #[error("Keyfile error: {0}")]
+   Keyfile(String),
+
+   #[error("{0} already exists")]
+   AlreadyExists(PathBuf),
+
+   #[error(
+      "Argon2 needs {} of memory but only {} are available",
+      super::memory::format_kib(needed / 1024),
+      super::memory::format_kib(available / 1024)
+   )]
+   NotEnoughMemory { needed: u64, available: u64 },
+
+   #[error("Cancelled")]
+   Cancelled,
+}
+
+impl From<std::io::Error> for Error {
+   fn from(e: std::io::Error) -> Self {
+      // The stream adapters have to report through io::Error, unwrap our own errors again
+      let is_ours = e.get_ref().is_some_and(|inner| inner.is::<Error>());
+      if is_ours {
+         return *e.into_inner().unwrap().downcast::<Error>().unwrap();
+      }
+      Self::Io(e)
+   }
+}
+
+impl Error {
+   pub fn read(path: impl Into<PathBuf>, source: std::io::Error) -> Self {
+      Self::Read {
+         path: path.into(),
+         source,
+      }
+   }
+
+   pub fn write(path: impl Into<PathBuf>, source: std::io::Error) -> Self {
+      Self::Write {
+         path: path.into(),
+         source,
+      }
+   }
+
+   pub fn shred(path: impl Into<PathBuf>, source: std::io::Error) -> Self {
+      Self::Shred {
+         path: path.into(),
+         source,
+      }
+   }
+}
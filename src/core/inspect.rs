@@ -0,0 +1,180 @@
+//! Reading what an encrypted file was made with, no credentials needed
+//!
+//! Useful to find out how much memory decrypting a file takes before committing to it.
+
+use super::{Error, age_format, encrypted_info, memory, read_encrypted_info, stream};
+use ncrypt_me::Argon2;
+use std::fmt;
+use std::fs::File;
+use std::io::BufReader;
+use std::path::Path;
+
+/// Every chunked file uses XChaCha20Poly1305 nonces
+const XNONCE_SIZE: usize = 24;
+
+/// The scrypt salt and the payload nonce of age files
+const AGE_SALT_SIZE: usize = 16;
+const AGE_NONCE_SIZE: usize = 16;
+
+/// The container format of an encrypted file
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Format {
+   /// The [chunked format](stream)
+   Chunked { version: u8, chunk_size: u32 },
+   /// The older format that encrypts the whole file at once
+   SingleShot,
+   /// The [age format](super::age_format)
+   Age,
+}
+
+impl fmt::Display for Format {
+   fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+      match self {
+         Self::Chunked {
+            version,
+            chunk_size,
+         } => write!(f, "Chunked v{} ({} KiB chunks)", version, chunk_size / 1024),
+         Self::SingleShot => write!(f, "Single shot"),
+         Self::Age => write!(f, "age v1"),
+      }
+   }
+}
+
+/// What [inspect] found out about a file
+#[derive(Clone, Debug)]
+pub struct FileInfo {
+   pub format: Format,
+   /// The Argon2 parameters of the first password slot, `None` for files only encrypted to
+   /// [recipients](super::recipient)
+   pub argon2: Option<Argon2>,
+   pub cipher: &'static str,
+   /// Whether decrypting needs [keyfiles](super::keyfile) as well
+   pub keyfile: bool,
+   /// The number of ways to open the file with credentials
+   pub password_slots: usize,
+   /// The number of [recipients](super::recipient) the file was encrypted to
+   pub recipients: usize,
+   /// The number of [recovery keys](super::recovery) that open the file
+   pub recovery_keys: usize,
+   /// The length of the password salt
+   pub salt_len: Option<usize>,
+   /// The length of the nonce the payload is encrypted with
+   pub nonce_len: usize,
+   /// The size of the encrypted payload without any headers
+   pub payload_size: u64,
+   pub file_size: u64,
+}
+
+impl FileInfo {
+   /// The memory Argon2 needs to derive the key, in bytes
+   pub fn memory_needed(&self) -> u64 {
+      self.argon2.as_ref().map(memory::needed).unwrap_or(0)
+   }
+
+   /// A warning if deriving the key needs more memory than is currently available
+   pub fn memory_warning(&self) -> Option<String> {
+      memory::check(self.argon2.as_ref()?)
+         .err()
+         .map(|e| e.to_string())
+   }
+}
+
+impl fmt::Display for FileInfo {
+   fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+      writeln!(f, "Format:        {}", self.format)?;
+      writeln!(f, "Cipher:        {}", self.cipher)?;
+      writeln!(
+         f,
+         "Keyfile:       {}",
+         if self.keyfile { "required" } else { "no" }
+      )?;
+      writeln!(f, "Passwords:     {}", self.password_slots)?;
+      writeln!(f, "Recipients:    {}", self.recipients)?;
+      writeln!(f, "Recovery keys: {}", self.recovery_keys)?;
+      if let Some(argon2) = &self.argon2 {
+         writeln!(
+            f,
+            "Memory cost:   {}",
+            memory::format_kib(argon2.m_cost as u64)
+         )?;
+         writeln!(f, "Iterations:    {}", argon2.t_cost)?;
+         writeln!(f, "Parallelism:   {}", argon2.p_cost)?;
+      }
+      if let Some(salt_len) = self.salt_len {
+         writeln!(f, "Salt length:   {} bytes", salt_len)?;
+      }
+      writeln!(f, "Nonce length:  {} bytes", self.nonce_len)?;
+      writeln!(f, "Payload size:  {} bytes", self.payload_size)?;
+      write!(f, "File size:     {} bytes", self.file_size)
+   }
+}
+
+/// Parses the headers of the encrypted file at `file_path` without decrypting anything
+pub fn inspect(file_path: &Path) -> Result<FileInfo, Error> {
+   let file_size = std::fs::metadata(file_path)
+      .map_err(|e| Error::read(file_path, e))?
+      .len();
+
+   if age_format::is_age_file(file_path)? {
+      let header = age_format::read_header(file_path)?;
+
+      return Ok(FileInfo {
+         format: Format::Age,
+         argon2: None,
+         cipher: "ChaCha20-Poly1305 (age STREAM)",
+         keyfile: false,
+         password_slots: header.passphrase as usize,
+         recipients: header.recipients,
+         recovery_keys: 0,
+         salt_len: header.passphrase.then_some(AGE_SALT_SIZE),
+         nonce_len: AGE_NONCE_SIZE,
+         payload_size: file_size.saturating_sub(header.size as u64),
+         file_size,
+      });
+   }
+
+   if stream::is_chunked_file(file_path)? {
+      let mut file = File::open(file_path).map_err(|e| Error::read(file_path, e))?;
+      let header = stream::Header::read_from(&mut file)?;
+
+      // The Argon2 parameters live in the wrapped data key
+      let info = match header.password_slots().next() {
+         Some(wrapped_key) => Some(encrypted_info(wrapped_key)?.0),
+         None => None,
+      };
+
+      return Ok(FileInfo {
+         format: Format::Chunked {
+            version: header.version,
+            chunk_size: header.chunk_size,
+         },
+         salt_len: info.as_ref().map(|info| info.password_salt.len()),
+         argon2: info.map(|info| info.argon2),
+         cipher: "XChaCha20-Poly1305 (STREAM)",
+         keyfile: header.needs_keyfile(),
+         password_slots: header.password_slots().count(),
+         recipients: header.recipient_count(),
+         recovery_keys: header.recovery_count(),
+         nonce_len: XNONCE_SIZE,
+         payload_size: file_size.saturating_sub(header.size() as u64),
+         file_size,
+      });
+   }
+
+   let file = File::open(file_path).map_err(|e| Error::read(file_path, e))?;
+   let (info, payload_start) = read_encrypted_info(BufReader::new(file))?;
+
+   Ok(FileInfo {
+      format: Format::SingleShot,
+      salt_len: Some(info.password_salt.len()),
+      nonce_len: info.cipher_nonce.len(),
+      argon2: Some(info.argon2),
+      password_slots: 1,
+      recipients: 0,
+      recovery_keys: 0,
+      cipher: "XChaCha20-Poly1305",
+      keyfile: false,
+      payload_size: file_size.saturating_sub(payload_start as u64),
+      file_size,
+   })
+}
@@ -0,0 +1,90 @@
+//! Keyfiles as a second factor next to the username and password
+//!
+//! The contents of every keyfile are hashed with SHA3-256, the sorted digests are hashed once more and the
+//! result is appended to the password before Argon2 runs, so the order the keyfiles are given in doesn't matter.
+//! Files encrypted this way carry [FLAG_KEYFILE](super::stream::FLAG_KEYFILE) in their header, so a missing
+//! keyfile is reported as such instead of as wrong credentials.
+//!
+//! The contents of a keyfile can also be given directly, e.g. scanned from a QR code, and count the same as a file
+//! holding them.
+
+use super::{Error, verify::digest};
+use ncrypt_me::{Credentials, secure_types::SecureString};
+use sha3::{Digest, Sha3_256};
+use std::fs::File;
+use std::io::BufReader;
+use std::path::PathBuf;
+use zeroize::Zeroizing;
+
+const HEX_DIGITS: &[u8; 16] = b"0123456789abcdef";
+
+/// A keyfile on disk or its contents
+#[derive(Clone, PartialEq, Eq)]
+pub enum Keyfile {
+   Path(PathBuf),
+   Contents(Zeroizing<Vec<u8>>),
+}
+
+impl From<PathBuf> for Keyfile {
+   fn from(path: PathBuf) -> Self {
+      Self::Path(path)
+   }
+}
+
+impl Keyfile {
+   /// The SHA3-256 digest of the contents
+   fn digest(&self) -> Result<[u8; 32], Error> {
+      match self {
+         Self::Path(path) => {
+            let file = File::open(path).map_err(|e| Error::read(path, e))?;
+            digest(BufReader::new(file)).map_err(|e| match e {
+               Error::Io(e) => Error::read(path, e),
+               e => e,
+            })
+         }
+         Self::Contents(contents) => digest(&contents[..]),
+      }
+   }
+}
+
+/// The combined digest of all `keyfiles`
+pub fn combined_digest(keyfiles: &[Keyfile]) -> Result<Zeroizing<[u8; 32]>, Error> {
+   let mut digests = Zeroizing::new(Vec::with_capacity(keyfiles.len()));
+   for keyfile in keyfiles {
+      digests.push(keyfile.digest()?);
+   }
+   digests.sort();
+
+   let mut hasher = Sha3_256::new();
+   for file_digest in digests.iter() {
+      hasher.update(file_digest);
+   }
+   Ok(Zeroizing::new(hasher.finalize().into()))
+}
+
+/// Builds the [Credentials] with the `keyfiles` mixed into the password
+///
+/// Without keyfiles this is the same as [Credentials::new] with the password confirmed.
+pub fn credentials(username: SecureString, password: SecureString, keyfiles: &[Keyfile]) -> Result<Credentials, Error> {
+   if keyfiles.is_empty() {
+      return Ok(Credentials::new(username, password.clone(), password));
+   }
+
+   let keyfile_digest = combined_digest(keyfiles)?;
+
+   let mut hex = Zeroizing::new([0u8; 64]);
+   for (i, byte) in keyfile_digest.iter().enumerate() {
+      hex[2 * i] = HEX_DIGITS[(byte >> 4) as usize];
+      hex[2 * i + 1] = HEX_DIGITS[(byte & 0x0f) as usize];
+   }
+
+   // Mixed straight into secure memory, no plain copy of the password is left behind
+   let mut mixed = SecureString::new_with_capacity(password.byte_len() + 1 + hex.len())
+      .map_err(|e| Error::Keyfile(e.to_string()))?;
+   password.unlock_str(|password| mixed.push_str(password));
+   mixed.push_str("\0");
+   // Only ASCII hex digits were written
+   mixed.push_str(std::str::from_utf8(&hex[..]).unwrap_or_default());
+
+   Ok(Credentials::new(username, mixed.clone(), mixed))
+}
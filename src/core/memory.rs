@@ -0,0 +1,84 @@
+//! How much memory the system has to spare for Argon2
+//!
+//! Argon2 asking for more memory than is available makes the system swap heavily or gets the process killed,
+//! so such jobs are refused unless [Options::ignore_memory_limit](super::Options::ignore_memory_limit) is set.
+
+use super::{Error, encrypted_info};
+use ncrypt_me::Argon2;
+
+/// The memory currently available to new processes in bytes, `None` where it can't be determined
+pub fn available() -> Option<u64> {
+   #[cfg(target_os = "linux")]
+   {
+      meminfo_field("MemAvailable")
+   }
+
+   #[cfg(not(target_os = "linux"))]
+   {
+      None
+   }
+}
+
+/// The physical memory of the system in bytes, `None` where it can't be determined
+pub fn total() -> Option<u64> {
+   #[cfg(target_os = "linux")]
+   {
+      meminfo_field("MemTotal")
+   }
+
+   #[cfg(not(target_os = "linux"))]
+   {
+      None
+   }
+}
+
+/// The memory Argon2 with `argon2` needs, in bytes, the memory cost is in KiB
+pub fn needed(argon2: &Argon2) -> u64 {
+   argon2.m_cost as u64 * 1024
+}
+
+/// Formats an amount of memory in KiB, like the Argon2 memory cost, as MiB or GiB
+pub fn format_kib(kib: u64) -> String {
+   if kib >= 1024 * 1024 {
+      format!("{:.1} GiB", kib as f64 / (1024.0 * 1024.0))
+   } else {
+      format!("{} MiB", kib / 1024)
+   }
+}
+
+/// Fails with [Error::NotEnoughMemory] if Argon2 with `argon2` needs more memory than is available,
+/// passes where that can't be determined
+pub fn check(argon2: &Argon2) -> Result<(), Error> {
+   let needed = needed(argon2);
+   match available() {
+      Some(available) if needed > available => Err(Error::NotEnoughMemory { needed, available }),
+      _ => Ok(()),
+   }
+}
+
+/// Like [check] with the parameters stored in data encrypted by [ncrypt_me]
+///
+/// Data that can't be parsed passes, decrypting it reports the actual problem.
+pub fn check_encrypted(data: &[u8]) -> Result<(), Error> {
+   match encrypted_info(data) {
+      Ok((info, _)) => check(&info.argon2),
+      Err(_) => Ok(()),
+   }
+}
+
+/// Reads a field of `/proc/meminfo` in bytes
+#[cfg(target_os = "linux")]
+fn meminfo_field(field: &str) -> Option<u64> {
+   let meminfo = std::fs::read_to_string("/proc/meminfo").ok()?;
+
+   meminfo.lines().find_map(|line| {
+      let value = line.strip_prefix(field)?.strip_prefix(':')?;
+      let kb = value
+         .trim()
+         .strip_suffix("kB")?
+         .trim()
+         .parse::<u64>()
+         .ok()?;
+      Some(kb * 1024)
+   })
+}
@@ -0,0 +1,156 @@
+//! The original name and attributes of an encrypted file, stored inside the encrypted payload
+//!
+//! In the [chunked format](super::stream) this record follows the
+//! [PayloadKind](super::PayloadKind) byte, so the ciphertext can be renamed freely without losing anything.
+//!
+//! | Field         | Size                   |
+//! |---------------|------------------------|
+//! | Name len      | 2 bytes (LE)           |
+//! | Name          | Name len bytes (UTF-8) |
+//! | Has mtime     | 1 byte                 |
+//! | Mtime seconds | 8 bytes (LE)           |
+//! | Mtime nanos   | 4 bytes (LE)           |
+//! | Has mode      | 1 byte                 |
+//! | Mode          | 4 bytes (LE)           |
+//!
+//! Missing values are written as zeroes, an empty name means the name is unknown.
+
+use super::{Error, decrypted_path};
+use std::ffi::OsStr;
+use std::fs::File;
+use std::io::{Read, Write};
+use std::path::{Component, Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+const MAX_NAME_LEN: usize = u16::MAX as usize;
+
+/// Only the permission bits are kept, never setuid, setgid or sticky
+const MODE_MASK: u32 = 0o777;
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Metadata {
+   /// The file name without any directories
+   pub name: Option<String>,
+   pub modified: Option<SystemTime>,
+   /// The Unix permission bits
+   pub mode: Option<u32>,
+}
+
+impl Metadata {
+   /// Collects the metadata of the file or directory at `path`
+   pub fn from_path(path: &Path) -> Result<Self, Error> {
+      let metadata = std::fs::metadata(path).map_err(|e| Error::read(path, e))?;
+
+      let name = path
+         .file_name()
+         .and_then(|name| name.to_str())
+         .filter(|name| name.len() <= MAX_NAME_LEN)
+         .map(String::from);
+
+      #[cfg(unix)]
+      let mode = {
+         use std::os::unix::fs::PermissionsExt;
+         Some(metadata.permissions().mode() & MODE_MASK)
+      };
+
+      #[cfg(not(unix))]
+      let mode = None;
+
+      Ok(Self {
+         name,
+         modified: metadata.modified().ok(),
+         mode,
+      })
+   }
+
+   pub fn write_to<W: Write>(&self, writer: &mut W) -> Result<(), Error> {
+      let name = self.name.as_deref().unwrap_or_default();
+      writer.write_all(&(name.len() as u16).to_le_bytes())?;
+      writer.write_all(name.as_bytes())?;
+
+      // Times before the epoch are rare enough to simply not store
+      let modified = self
+         .modified
+         .and_then(|time| time.duration_since(UNIX_EPOCH).ok());
+      writer.write_all(&[modified.is_some() as u8])?;
+      writer.write_all(&modified.map(|d| d.as_secs()).unwrap_or(0).to_le_bytes())?;
+      writer.write_all(
+         &modified
+            .map(|d| d.subsec_nanos())
+            .unwrap_or(0)
+            .to_le_bytes(),
+      )?;
+
+      writer.write_all(&[self.mode.is_some() as u8])?;
+      writer.write_all(&self.mode.unwrap_or(0).to_le_bytes())?;
+      Ok(())
+   }
+
+   /// Reads the record, a stored name that isn't a plain file name is dropped
+   pub fn read_from<R: Read>(reader: &mut R) -> Result<Self, Error> {
+      let mut len = [0u8; 2];
+      reader.read_exact(&mut len)?;
+      let mut name = vec![0u8; u16::from_le_bytes(len) as usize];
+      reader.read_exact(&mut name)?;
+      let name = String::from_utf8(name)
+         .ok()
+         .filter(|name| is_plain_name(name));
+
+      let mut has_modified = [0u8; 1];
+      let mut secs = [0u8; 8];
+      let mut nanos = [0u8; 4];
+      reader.read_exact(&mut has_modified)?;
+      reader.read_exact(&mut secs)?;
+      reader.read_exact(&mut nanos)?;
+      let nanos = u32::from_le_bytes(nanos);
+      let modified = if has_modified[0] == 1 && nanos < 1_000_000_000 {
+         UNIX_EPOCH.checked_add(Duration::new(u64::from_le_bytes(secs), nanos))
+      } else {
+         None
+      };
+
+      let mut has_mode = [0u8; 1];
+      let mut mode = [0u8; 4];
+      reader.read_exact(&mut has_mode)?;
+      reader.read_exact(&mut mode)?;
+      let mode = (has_mode[0] == 1).then(|| u32::from_le_bytes(mode) & MODE_MASK);
+
+      Ok(Self {
+         name,
+         modified,
+         mode,
+      })
+   }
+
+   /// Where a decrypted copy of `file_path` goes, next to it under the stored name if there is one
+   pub fn restored_path(&self, file_path: &Path) -> PathBuf {
+      match &self.name {
+         Some(name) if file_path.file_name() != Some(OsStr::new(name)) => file_path.with_file_name(name),
+         _ => decrypted_path(file_path),
+      }
+   }
+
+   /// Restores the modification time and permissions of the file at `path`, as far as the platform allows
+   ///
+   /// This is best effort, failures are ignored since the contents are what matters.
+   pub fn apply(&self, path: &Path) {
+      // The time goes first as the permissions may make the file read only
+      if let Some(modified) = self.modified
+         && let Ok(file) = File::options().write(true).open(path)
+      {
+         let _ = file.set_modified(modified);
+      }
+
+      #[cfg(unix)]
+      if let Some(mode) = self.mode {
+         use std::os::unix::fs::PermissionsExt;
+         let _ = std::fs::set_permissions(path, std::fs::Permissions::from_mode(mode));
+      }
+   }
+}
+
+/// Whether `name` is a single normal path component, so it can't point anywhere but next to the ciphertext
+fn is_plain_name(name: &str) -> bool {
+   let mut components = Path::new(name).components();
+   matches!(components.next(), Some(Component::Normal(_))) && components.next().is_none() && !name.contains(['/', '\\'])
+}
@@ -0,0 +1,514 @@
+//! GUI independent file encryption and decryption
+//!
+//! Everything here runs synchronously on the calling thread and reports through return values,
+//! so the GUI, the CLI and tests can all drive the same code.
+
+use chacha20poly1305::aead::{OsRng, rand_core::RngCore};
+use ncrypt_me::{
+   Argon2, Credentials, EncryptedInfo, RECOMMENDED_SALT_LEN, decrypt::decrypt_data_unsecured, encrypt::encrypt_data_ref,
+};
+use std::fs::File;
+use std::io::{BufReader, Read, Write};
+use std::path::{Path, PathBuf};
+use zeroize::Zeroize;
+
+pub mod age_format;
+pub mod archive;
+pub mod batch;
+pub mod calibrate;
+pub mod error;
+pub mod inspect;
+pub mod keyfile;
+pub mod memory;
+pub mod metadata;
+pub mod output;
+pub mod preset;
+pub mod progress;
+pub mod qr;
+pub mod recipient;
+pub mod recovery;
+pub mod rekey;
+pub mod shamir;
+pub mod shred;
+pub mod slots;
+pub mod stream;
+pub mod verify;
+
+pub use archive::{encrypt_dir, encrypt_dir_with_key};
+pub use error::Error;
+pub use inspect::{FileInfo, inspect};
+pub use metadata::Metadata;
+pub use output::Overwrite;
+use output::write_file;
+pub use preset::Preset;
+pub use progress::{Phase, Progress};
+use progress::{ProgressReader, ProgressWriter};
+pub use recipient::{Identity, Recipient};
+pub use recovery::RecoveryKey;
+pub use stream::Secret;
+use stream::{DataKey, Decryptor, Encryptor, Header, KeyCache};
+
+/// The extension appended to encrypted files
+pub const FILE_EXTENSION: &str = ".ncrypt";
+
+/// The extension appended to decrypted files whose name doesn't end with [FILE_EXTENSION]
+pub const DECRYPTED_EXTENSION: &str = ".decrypted";
+
+/// The magic and the length of the [EncryptedInfo] in front of data encrypted by [ncrypt_me]
+const ENCRYPTED_INFO_START: usize = 12;
+
+/// Two salts, the nonce and the encoded Argon2 parameters
+const ENCRYPTED_INFO_LEN: usize = 2 * RECOMMENDED_SALT_LEN + 24 + 28;
+
+/// Settings shared by all operations
+#[derive(Clone, Debug, Default)]
+pub struct Options {
+   /// What to do when the output already exists
+   pub overwrite: Overwrite,
+   /// Write the output exactly here, only makes sense when processing a single file
+   pub output_path: Option<PathBuf>,
+   /// Write the outputs into this directory instead of next to the inputs
+   pub output_dir: Option<PathBuf>,
+   /// Give encrypted files a random name, the original one is only kept inside the [Metadata]
+   pub neutral_name: bool,
+   /// Decrypt every encrypted file again with the same credentials and compare it against the original
+   pub verify: bool,
+   /// Remove the originals once their encrypted copies are [verified](verify), see [shred] for the caveats
+   pub shred: bool,
+   /// Run Argon2 even if it needs more [memory] than is available
+   pub ignore_memory_limit: bool,
+   /// Whether the credentials have [keyfiles](keyfile) mixed in
+   pub keyfile: bool,
+}
+
+impl Options {
+   /// Where an output that would by default go to `default_path` is written
+   pub fn output_for(&self, default_path: PathBuf) -> PathBuf {
+      if let Some(path) = &self.output_path {
+         return path.clone();
+      }
+
+      match (&self.output_dir, default_path.file_name()) {
+         (Some(dir), Some(name)) => dir.join(name),
+         _ => default_path,
+      }
+   }
+
+   /// Whether encrypted files are verified, shredding always verifies first
+   pub fn verifies(&self) -> bool {
+      self.verify || self.shred
+   }
+
+   /// Where the encrypted copy of the file or directory at `file_path` is written
+   pub fn encrypted_output(&self, file_path: &Path) -> PathBuf {
+      if self.neutral_name {
+         self.output_for(neutral_path(file_path))
+      } else {
+         self.output_for(encrypted_path(file_path))
+      }
+   }
+}
+
+/// The path an encrypted copy of the file or directory at `file_path` is written to
+pub fn encrypted_path(file_path: &Path) -> PathBuf {
+   let mut path = file_path.as_os_str().to_owned();
+   path.push(FILE_EXTENSION);
+   PathBuf::from(path)
+}
+
+/// A random path next to `file_path` that says nothing about the original name
+pub fn neutral_path(file_path: &Path) -> PathBuf {
+   let mut bytes = [0u8; 8];
+   OsRng.fill_bytes(&mut bytes);
+   let name: String = bytes.iter().map(|b| format!("{:02x}", b)).collect();
+   file_path.with_file_name(format!("{}{}", name, FILE_EXTENSION))
+}
+
+/// The path a decrypted copy of `file_path` is written to when its original name is unknown
+///
+/// Only a trailing [FILE_EXTENSION] is removed, any other name gets [DECRYPTED_EXTENSION] appended
+/// so the plaintext never lands on the path of the ciphertext.
+pub fn decrypted_path(file_path: &Path) -> PathBuf {
+   strip_extension(file_path, FILE_EXTENSION)
+}
+
+/// Removes a trailing `extension` from `file_path`, or appends [DECRYPTED_EXTENSION] if there is none
+fn strip_extension(file_path: &Path, extension: &str) -> PathBuf {
+   let stripped = file_path
+      .file_name()
+      .and_then(|name| name.to_str())
+      .and_then(|name| name.strip_suffix(extension))
+      .filter(|name| !name.is_empty());
+
+   match stripped {
+      Some(name) => file_path.with_file_name(name),
+      None => {
+         let mut path = file_path.as_os_str().to_owned();
+         path.push(DECRYPTED_EXTENSION);
+         PathBuf::from(path)
+      }
+   }
+}
+
+/// Encrypts the data in memory
+pub fn encrypt_bytes(argon2: Argon2, data: &[u8], credentials: Credentials) -> Result<Vec<u8>, Error> {
+   encrypt_data_ref(argon2, data, credentials).map_err(|e| Error::Encrypt(e.to_string()))
+}
+
+/// Decrypts the data in memory
+///
+/// The caller is responsible for zeroizing the returned plaintext
+pub fn decrypt_bytes(encrypted_data: Vec<u8>, credentials: Credentials) -> Result<Vec<u8>, Error> {
+   encrypted_info(&encrypted_data)?;
+   decrypt_data_unsecured(encrypted_data, credentials).map_err(|e| Error::Decrypt(e.to_string()))
+}
+
+/// Reads the [EncryptedInfo] in front of data encrypted by [ncrypt_me], along with where the ciphertext starts
+///
+/// [ncrypt_me] slices the data without checking its length, so the lengths are checked here first and truncated
+/// data fails with [Error::InvalidFormat] instead of panicking.
+pub fn encrypted_info(data: &[u8]) -> Result<(EncryptedInfo, usize), Error> {
+   let invalid = || Error::InvalidFormat("The encrypted data is truncated".to_string());
+
+   let info_len = data
+      .get(ENCRYPTED_INFO_START - 4..ENCRYPTED_INFO_START)
+      .ok_or_else(invalid)?;
+   let info_len = u32::from_le_bytes(info_len.try_into().unwrap()) as usize;
+
+   let payload_start = ENCRYPTED_INFO_START.saturating_add(info_len);
+   if info_len < ENCRYPTED_INFO_LEN || payload_start > data.len() {
+      return Err(invalid());
+   }
+
+   let info = EncryptedInfo::from_encrypted_data(data).map_err(|e| Error::InvalidFormat(e.to_string()))?;
+   Ok((info, payload_start))
+}
+
+/// Like [encrypted_info], but reads only the length prefix and the [EncryptedInfo] from `reader`
+///
+/// The info is read through [Read::take], so a corrupt length can't make it allocate more than the file holds.
+pub fn read_encrypted_info<R: Read>(mut reader: R) -> Result<(EncryptedInfo, usize), Error> {
+   let mut header = vec![0u8; ENCRYPTED_INFO_START];
+   reader
+      .read_exact(&mut header)
+      .map_err(|_| Error::InvalidFormat("The encrypted data is truncated".to_string()))?;
+
+   let info_len = u32::from_le_bytes(header[ENCRYPTED_INFO_START - 4..].try_into().unwrap());
+   reader
+      .take(info_len as u64)
+      .read_to_end(&mut header)
+      .map_err(|e| Error::InvalidFormat(e.to_string()))?;
+
+   encrypted_info(&header)
+}
+
+/// What the payload of a chunked file holds, stored as its first plaintext byte
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PayloadKind {
+   /// The contents of a single file
+   File = 0,
+   /// A tar [archive] of a directory
+   Directory = 1,
+}
+
+impl PayloadKind {
+   pub fn from_byte(byte: u8) -> Result<Self, Error> {
+      match byte {
+         0 => Ok(Self::File),
+         1 => Ok(Self::Directory),
+         _ => Err(Error::InvalidFormat(format!(
+            "Unknown payload kind {}",
+            byte
+         ))),
+      }
+   }
+}
+
+/// Encrypts the file at `file_path` in the [chunked format](stream) and writes the result to [encrypted_path],
+/// unless the [Options] say otherwise
+///
+/// Returns the path of the encrypted file
+pub fn encrypt_file(
+   argon2: Argon2,
+   file_path: &Path,
+   credentials: Credentials,
+   options: &Options,
+) -> Result<PathBuf, Error> {
+   let progress = Progress::new();
+   let key = derive_key(argon2, credentials, options, &progress)?;
+   encrypt_file_with_key(&key, file_path, options, &progress)
+}
+
+/// Encrypts the file at `file_path` so only the holders of the identities behind `recipients` can decrypt it,
+/// otherwise the same as [encrypt_file]
+pub fn encrypt_file_to(recipients: &[Recipient], file_path: &Path, options: &Options) -> Result<PathBuf, Error> {
+   let key = DataKey::for_recipients(recipients)?;
+   encrypt_file_with_key(&key, file_path, options, &Progress::new())
+}
+
+/// Generates the [DataKey] for an encryption, when verifying also checks that `credentials` unwrap it again
+///
+/// Together with the per file [verify::verify] this amounts to decrypting with the same credentials,
+/// while Argon2 only runs twice no matter how many files there are.
+pub fn derive_key(
+   argon2: Argon2,
+   credentials: Credentials,
+   options: &Options,
+   progress: &Progress,
+) -> Result<DataKey, Error> {
+   if !options.ignore_memory_limit {
+      memory::check(&argon2)?;
+   }
+
+   progress.set_phase(Phase::DerivingKey);
+   let mut key = DataKey::generate(argon2, credentials.clone())?;
+   progress.check()?;
+
+   if options.keyfile {
+      key.flags |= stream::FLAG_KEYFILE;
+   }
+
+   if options.verifies() {
+      progress.set_phase(Phase::Verifying);
+      key.check(credentials)?;
+      progress.check()?;
+   }
+   Ok(key)
+}
+
+/// Like [encrypt_file] but with an already derived [DataKey], reporting to `progress`
+pub fn encrypt_file_with_key(
+   key: &DataKey,
+   file_path: &Path,
+   options: &Options,
+   progress: &Progress,
+) -> Result<PathBuf, Error> {
+   if options.shred {
+      shred::check_original(file_path)?;
+   }
+
+   let file = File::open(file_path).map_err(|e| Error::read(file_path, e))?;
+   let total = file.metadata().map(|m| m.len()).unwrap_or(0);
+   let mut reader = BufReader::new(file);
+
+   let metadata = Metadata::from_path(file_path)?;
+
+   let new_file_path = options.encrypted_output(file_path);
+   let new_file_path = write_file(&new_file_path, options.overwrite, |writer| {
+      let mut encryptor = Encryptor::new(key, writer)?;
+      encryptor.write_all(&[PayloadKind::File as u8])?;
+      metadata.write_to(&mut encryptor)?;
+
+      progress.start(Phase::Encrypting, total);
+      let mut writer = ProgressWriter::new(encryptor, progress);
+      std::io::copy(&mut reader, &mut writer)?;
+
+      progress.set_phase(Phase::Writing);
+      writer.into_inner().finish()?;
+      Ok(())
+   })?;
+
+   finish_encryption(key, file_path, &new_file_path, options, progress)?;
+   Ok(new_file_path)
+}
+
+/// Verifies the encrypted copy and shreds the original as far as the [Options] ask for it
+fn finish_encryption(
+   key: &DataKey,
+   original_path: &Path,
+   encrypted_path: &Path,
+   options: &Options,
+   progress: &Progress,
+) -> Result<(), Error> {
+   if !options.verifies() {
+      return Ok(());
+   }
+
+   verify::verify(key, original_path, encrypted_path, progress)?;
+   progress.check()?;
+
+   if !options.shred {
+      return Ok(());
+   }
+
+   let total = if original_path.is_dir() {
+      archive::dir_totals(original_path).1
+   } else {
+      std::fs::symlink_metadata(original_path)
+         .map(|m| m.len())
+         .unwrap_or(0)
+   };
+   progress.start(Phase::Shredding, total);
+   shred::shred(original_path, progress)
+}
+
+/// Decrypts the file at `file_path` and writes the result next to it under its original name,
+/// unless the [Options] say otherwise
+///
+/// The stored modification time and permissions are restored as well. Files without a stored name
+/// are written to [decrypted_path].
+///
+/// Both the [chunked format](stream) and the older single shot format are supported,
+/// encrypted directories are restored with [archive::unpack]
+///
+/// `secret` is either the [Credentials] or the [Identity] the file was encrypted to, or an
+/// [AgeSecret](age_format::AgeSecret) for files in the [age format](age_format).
+///
+/// Returns the path of the decrypted file or directory
+pub fn decrypt_file(file_path: &Path, secret: impl Into<Secret>, options: &Options) -> Result<PathBuf, Error> {
+   decrypt_file_with_cache(
+      file_path,
+      &secret.into(),
+      &mut KeyCache::default(),
+      options,
+      &Progress::new(),
+   )
+}
+
+/// Like [decrypt_file] but reuses keys already unwrapped by earlier files, reporting to `progress`
+pub fn decrypt_file_with_cache(
+   file_path: &Path,
+   secret: &Secret,
+   cache: &mut KeyCache,
+   options: &Options,
+   progress: &Progress,
+) -> Result<PathBuf, Error> {
+   if let Secret::Age(secret) = secret {
+      return age_format::decrypt_file(secret, file_path, options, progress);
+   }
+
+   if age_format::is_age_file(file_path)? {
+      return Err(Error::Decrypt(
+         "This is an age file, turn on the age format to decrypt it".to_string(),
+      ));
+   }
+
+   if !stream::is_chunked_file(file_path)? {
+      let Secret::Credentials(credentials) = secret else {
+         return Err(Error::Decrypt(
+            "Files in the old single shot format can only be decrypted with credentials".to_string(),
+         ));
+      };
+
+      if options.keyfile {
+         return Err(Error::Keyfile(
+            "Files in the old single shot format can't use keyfiles".to_string(),
+         ));
+      }
+
+      let new_file_path = options.output_for(decrypted_path(file_path));
+
+      progress.set_phase(Phase::Reading);
+      let encrypted_data = std::fs::read(file_path).map_err(|e| Error::read(file_path, e))?;
+      progress.check()?;
+
+      if !options.ignore_memory_limit {
+         memory::check_encrypted(&encrypted_data)?;
+      }
+
+      // The single shot format derives the key and decrypts in one go
+      progress.set_phase(Phase::Decrypting);
+      let mut decrypted_data = decrypt_bytes(encrypted_data, credentials.clone())?;
+      if let Err(e) = progress.check() {
+         decrypted_data.zeroize();
+         return Err(e);
+      }
+
+      progress.set_phase(Phase::Writing);
+      let res = write_file(&new_file_path, options.overwrite, |writer| {
+         writer.write_all(&decrypted_data)?;
+         Ok(())
+      });
+      decrypted_data.zeroize();
+
+      return res;
+   }
+
+   let file = File::open(file_path).map_err(|e| Error::read(file_path, e))?;
+   let total = file.metadata().map(|m| m.len()).unwrap_or(0);
+   let mut reader = BufReader::new(file);
+   let header = Header::read_from(&mut reader)?;
+
+   check_unlock(&header, secret, options, cache.contains(&header.slots))?;
+
+   progress.set_phase(Phase::DerivingKey);
+   let key = cache
+      .get_or_unwrap(&header.slots, secret)
+      .map_err(|e| unlock_error(e, &header, secret))?;
+   progress.check()?;
+
+   // Count the ciphertext, the total is the size of the file
+   progress.start(
+      Phase::Decrypting,
+      total.saturating_sub(header.size() as u64),
+   );
+   let reader = ProgressReader::new(reader, progress);
+   let mut reader = Decryptor::new(key, header, reader)?;
+
+   let (kind, metadata) = read_payload_start(&mut reader)?;
+   let new_file_path = options.output_for(metadata.restored_path(file_path));
+
+   match kind {
+      PayloadKind::File => {
+         let new_file_path = write_file(&new_file_path, options.overwrite, |writer| {
+            std::io::copy(&mut reader, writer)?;
+            progress.set_phase(Phase::Writing);
+            Ok(())
+         })?;
+         metadata.apply(&new_file_path);
+         Ok(new_file_path)
+      }
+      // The archive carries the attributes of the directory itself
+      PayloadKind::Directory => archive::unpack(reader, &new_file_path, options.overwrite),
+   }
+}
+
+/// The checks before unwrapping the key of a chunked file, only credentials run Argon2 and use keyfiles
+///
+/// The memory check is skipped if the key is `cached` already.
+fn check_unlock(header: &Header, secret: &Secret, options: &Options, cached: bool) -> Result<(), Error> {
+   if let Secret::Credentials(_) = secret {
+      if !options.ignore_memory_limit && !cached {
+         for wrapped_key in header.password_slots() {
+            memory::check_encrypted(wrapped_key)?;
+         }
+      }
+
+      check_keyfile(header, options)?;
+   }
+   Ok(())
+}
+
+/// Points at the keyfiles as well when credentials fail on a file that needs them
+fn unlock_error(e: Error, header: &Header, secret: &Secret) -> Error {
+   match e {
+      Error::Decrypt(e) if header.needs_keyfile() && matches!(secret, Secret::Credentials(_)) => {
+         Error::Decrypt(format!("{}, check the credentials and keyfiles", e))
+      }
+      e => e,
+   }
+}
+
+/// Fails with [Error::Keyfile] if the keyfile selection doesn't match what the file was encrypted with
+fn check_keyfile(header: &Header, options: &Options) -> Result<(), Error> {
+   match (header.needs_keyfile(), options.keyfile) {
+      (true, false) => Err(Error::Keyfile(
+         "This file was encrypted with keyfiles, select them to decrypt it".to_string(),
+      )),
+      (false, true) => Err(Error::Keyfile(
+         "This file was encrypted without keyfiles, deselect them to decrypt it".to_string(),
+      )),
+      _ => Ok(()),
+   }
+}
+
+/// Reads the [PayloadKind] and the [Metadata] that start every payload
+fn read_payload_start<R: Read>(decryptor: &mut Decryptor<R>) -> Result<(PayloadKind, Metadata), Error> {
+   let mut kind = [0u8; 1];
+   decryptor.read_exact(&mut kind)?;
+   let kind = PayloadKind::from_byte(kind[0])?;
+
+   let metadata = Metadata::read_from(decryptor)?;
+
+   Ok((kind, metadata))
+}
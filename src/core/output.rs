@@ -0,0 +1,223 @@
+//! Writing results to disk without ever leaving a partial file behind
+//!
+//! Everything is first written to a temporary file (or directory) next to the destination, synced, and then
+//! renamed into place, so a crash or an error midway leaves the destination untouched.
+
+use super::Error;
+use std::io::{BufWriter, ErrorKind, Write};
+use std::path::{Path, PathBuf};
+use tempfile::NamedTempFile;
+
+const TEMP_PREFIX: &str = ".ncrypt-";
+
+/// What to do when the output path already exists
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Overwrite {
+   /// Fail with [Error::AlreadyExists]
+   #[default]
+   Never,
+   /// Replace what is there
+   Replace,
+   /// Write next to it under a free name like `report (1).pdf`
+   KeepBoth,
+}
+
+/// Where the output for `path` ends up under the given policy
+pub fn target_path(path: &Path, overwrite: Overwrite) -> Result<PathBuf, Error> {
+   if !exists(path) {
+      return Ok(path.to_path_buf());
+   }
+
+   match overwrite {
+      Overwrite::Never => Err(Error::AlreadyExists(path.to_path_buf())),
+      Overwrite::Replace => Ok(path.to_path_buf()),
+      Overwrite::KeepBoth => Ok(unique_path(path)),
+   }
+}
+
+/// The first free path of the form `name (n).ext`, the number goes before every extension so
+/// `report.pdf.ncrypt` becomes `report (1).pdf.ncrypt`
+pub fn unique_path(path: &Path) -> PathBuf {
+   let file_name = path
+      .file_name()
+      .map(|name| name.to_string_lossy().to_string())
+      .unwrap_or_default();
+
+   // Skip a leading dot so hidden files keep their name
+   let split = file_name
+      .char_indices()
+      .skip(1)
+      .find(|(_, c)| *c == '.')
+      .map(|(i, _)| i)
+      .unwrap_or(file_name.len());
+   let (base, extensions) = file_name.split_at(split);
+
+   let mut n = 1;
+   loop {
+      let candidate = path.with_file_name(format!("{} ({}){}", base, n, extensions));
+      if !exists(&candidate) {
+         return candidate;
+      }
+      n += 1;
+   }
+}
+
+/// Writes a file at `path` (or wherever `overwrite` says) through a temporary file
+///
+/// Returns the path actually written
+pub fn write_file<F>(path: &Path, overwrite: Overwrite, write: F) -> Result<PathBuf, Error>
+where
+   F: FnOnce(&mut BufWriter<NamedTempFile>) -> Result<(), Error>,
+{
+   write_temp_file(path, overwrite, None, write)
+}
+
+/// Replaces the existing file at `path` through a temporary file like [write_file], keeping its permissions and
+/// modification time
+pub fn rewrite_file<F>(path: &Path, write: F) -> Result<(), Error>
+where
+   F: FnOnce(&mut BufWriter<NamedTempFile>) -> Result<(), Error>,
+{
+   let original = std::fs::metadata(path).map_err(|e| Error::read(path, e))?;
+   write_temp_file(path, Overwrite::Replace, Some(&original), write)?;
+   Ok(())
+}
+
+fn write_temp_file<F>(
+   path: &Path,
+   overwrite: Overwrite,
+   original: Option<&std::fs::Metadata>,
+   write: F,
+) -> Result<PathBuf, Error>
+where
+   F: FnOnce(&mut BufWriter<NamedTempFile>) -> Result<(), Error>,
+{
+   let target = target_path(path, overwrite)?;
+   let dir = parent_dir(&target);
+
+   let temp = tempfile::Builder::new()
+      .prefix(TEMP_PREFIX)
+      .suffix(".tmp")
+      .tempfile_in(dir)
+      .map_err(|e| Error::write(&target, e))?;
+
+   // On any error the temporary file is removed when dropped
+   let mut writer = BufWriter::new(temp);
+   write(&mut writer)?;
+   writer.flush().map_err(|e| Error::write(&target, e))?;
+
+   let temp = writer
+      .into_inner()
+      .map_err(|e| Error::write(&target, e.into_error()))?;
+   temp
+      .as_file()
+      .sync_all()
+      .map_err(|e| Error::write(&target, e))?;
+
+   // The file is still open for writing, so a read only mode doesn't get in the way
+   if let Some(original) = original {
+      let file = temp.as_file();
+      if let Ok(modified) = original.modified() {
+         file
+            .set_modified(modified)
+            .map_err(|e| Error::write(&target, e))?;
+      }
+      file
+         .set_permissions(original.permissions())
+         .map_err(|e| Error::write(&target, e))?;
+   }
+
+   let res = if overwrite == Overwrite::Replace {
+      temp.persist(&target)
+   } else {
+      temp.persist_noclobber(&target)
+   };
+
+   res.map_err(|e| {
+      if e.error.kind() == ErrorKind::AlreadyExists {
+         Error::AlreadyExists(target.clone())
+      } else {
+         Error::write(&target, e.error)
+      }
+   })?;
+
+   sync_dir(dir);
+   Ok(target)
+}
+
+/// Fills a temporary directory with `write` and moves it to `path` (or wherever `overwrite` says)
+///
+/// Replacing an existing directory first moves it aside, so this is not atomic as a whole, but the old directory
+/// is only deleted once the new one is in place.
+///
+/// Returns the path actually written
+pub fn write_dir<F>(path: &Path, overwrite: Overwrite, write: F) -> Result<PathBuf, Error>
+where
+   F: FnOnce(&Path) -> Result<(), Error>,
+{
+   let target = target_path(path, overwrite)?;
+   let dir = parent_dir(&target);
+
+   let temp = tempfile::Builder::new()
+      .prefix(TEMP_PREFIX)
+      .tempdir_in(dir)
+      .map_err(|e| Error::write(&target, e))?;
+
+   // On any error the temporary directory is removed when dropped
+   write(temp.path())?;
+   let temp_path = temp.keep();
+
+   let res = if exists(&target) {
+      replace_dir(&temp_path, &target)
+   } else {
+      std::fs::rename(&temp_path, &target)
+   };
+
+   if let Err(e) = res {
+      let _ = std::fs::remove_dir_all(&temp_path);
+      return Err(Error::write(&target, e));
+   }
+
+   sync_dir(dir);
+   Ok(target)
+}
+
+fn replace_dir(new: &Path, target: &Path) -> std::io::Result<()> {
+   let file_name = target.file_name().unwrap_or_default().to_string_lossy();
+   let backup = unique_path(&target.with_file_name(format!("{}old-{}", TEMP_PREFIX, file_name)));
+
+   std::fs::rename(target, &backup)?;
+
+   if let Err(e) = std::fs::rename(new, target) {
+      let _ = std::fs::rename(&backup, target);
+      return Err(e);
+   }
+
+   if backup.is_dir() {
+      std::fs::remove_dir_all(&backup)
+   } else {
+      std::fs::remove_file(&backup)
+   }
+}
+
+fn exists(path: &Path) -> bool {
+   path.symlink_metadata().is_ok()
+}
+
+fn parent_dir(path: &Path) -> &Path {
+   match path.parent() {
+      Some(parent) if !parent.as_os_str().is_empty() => parent,
+      _ => Path::new("."),
+   }
+}
+
+/// Makes the rename itself durable, only possible on Unix
+fn sync_dir(dir: &Path) {
+   #[cfg(unix)]
+   if let Ok(dir) = std::fs::File::open(dir) {
+      let _ = dir.sync_all();
+   }
+
+   #[cfg(not(unix))]
+   let _ = dir;
+}
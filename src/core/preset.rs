@@ -0,0 +1,65 @@
+//! Named Argon2 parameter sets
+
+use ncrypt_me::Argon2;
+use std::fmt;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Preset {
+   Fast,
+   Balanced,
+   Strong,
+   Paranoid,
+   /// Parameters that match none of the others
+   Custom,
+}
+
+impl Preset {
+   pub const ALL: [Preset; 5] = [
+      Preset::Fast,
+      Preset::Balanced,
+      Preset::Strong,
+      Preset::Paranoid,
+      Preset::Custom,
+   ];
+
+   /// The parameters of the preset, `None` for [Preset::Custom]
+   pub fn argon2(&self) -> Option<Argon2> {
+      match self {
+         Self::Fast => Some(Argon2::fast()),
+         Self::Balanced => Some(Argon2::balanced()),
+         Self::Strong => Some(Argon2::slow()),
+         Self::Paranoid => Some(Argon2::very_slow()),
+         Self::Custom => None,
+      }
+   }
+
+   /// The preset `argon2` belongs to
+   pub fn from_argon2(argon2: &Argon2) -> Self {
+      Self::ALL
+         .into_iter()
+         .find(|preset| {
+            preset.argon2().is_some_and(|params| {
+               params.m_cost == argon2.m_cost && params.t_cost == argon2.t_cost && params.p_cost == argon2.p_cost
+            })
+         })
+         .unwrap_or(Self::Custom)
+   }
+
+   pub fn from_name(name: &str) -> Option<Self> {
+      Self::ALL
+         .into_iter()
+         .find(|preset| preset.to_string().eq_ignore_ascii_case(name))
+   }
+}
+
+impl fmt::Display for Preset {
+   fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+      match self {
+         Self::Fast => write!(f, "Fast"),
+         Self::Balanced => write!(f, "Balanced"),
+         Self::Strong => write!(f, "Strong"),
+         Self::Paranoid => write!(f, "Paranoid"),
+         Self::Custom => write!(f, "Custom"),
+      }
+   }
+}
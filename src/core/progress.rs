@@ -0,0 +1,202 @@
+//! Progress reporting and cancellation of running jobs
+//!
+//! A [Progress] is a cheap handle shared between the thread doing the work and whoever watches it.
+//! The work side wraps its streams in [ProgressReader] or [ProgressWriter], which count the bytes passing
+//! through and abort with [Error::Cancelled] as soon as [Progress::cancel] is called.
+//!
+//! Argon2 itself can not be interrupted, a cancel during [Phase::DerivingKey] takes effect once it returns.
+
+use super::Error;
+use std::fmt;
+use std::io::{Read, Write};
+use std::sync::{
+   Arc, Mutex,
+   atomic::{AtomicBool, AtomicU64, Ordering},
+};
+use std::time::{Duration, Instant};
+
+/// What a job is currently doing
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Phase {
+   Idle,
+   DerivingKey,
+   Reading,
+   Encrypting,
+   Decrypting,
+   Writing,
+   Verifying,
+   Shredding,
+   Calibrating,
+}
+
+impl fmt::Display for Phase {
+   fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+      match self {
+         Self::Idle => write!(f, "Waiting"),
+         Self::DerivingKey => write!(f, "Deriving key"),
+         Self::Reading => write!(f, "Reading"),
+         Self::Encrypting => write!(f, "Encrypting"),
+         Self::Decrypting => write!(f, "Decrypting"),
+         Self::Writing => write!(f, "Writing"),
+         Self::Verifying => write!(f, "Verifying"),
+         Self::Shredding => write!(f, "Shredding original"),
+         Self::Calibrating => write!(f, "Benchmarking Argon2"),
+      }
+   }
+}
+
+struct State {
+   phase: Phase,
+   started: Instant,
+}
+
+struct Inner {
+   state: Mutex<State>,
+   bytes_done: AtomicU64,
+   bytes_total: AtomicU64,
+   cancelled: AtomicBool,
+}
+
+#[derive(Clone)]
+pub struct Progress(Arc<Inner>);
+
+impl Default for Progress {
+   fn default() -> Self {
+      Self(Arc::new(Inner {
+         state: Mutex::new(State {
+            phase: Phase::Idle,
+            started: Instant::now(),
+         }),
+         bytes_done: AtomicU64::new(0),
+         bytes_total: AtomicU64::new(0),
+         cancelled: AtomicBool::new(false),
+      }))
+   }
+}
+
+impl Progress {
+   pub fn new() -> Self {
+      Self::default()
+   }
+
+   pub fn phase(&self) -> Phase {
+      self.0.state.lock().unwrap().phase
+   }
+
+   pub fn set_phase(&self, phase: Phase) {
+      self.0.state.lock().unwrap().phase = phase;
+   }
+
+   /// Starts counting towards `total` bytes from zero, the ETA is measured from here
+   pub fn start(&self, phase: Phase, total: u64) {
+      let mut state = self.0.state.lock().unwrap();
+      state.phase = phase;
+      state.started = Instant::now();
+      self.0.bytes_done.store(0, Ordering::Relaxed);
+      self.0.bytes_total.store(total, Ordering::Relaxed);
+   }
+
+   pub fn advance(&self, bytes: u64) {
+      self.0.bytes_done.fetch_add(bytes, Ordering::Relaxed);
+   }
+
+   pub fn bytes_done(&self) -> u64 {
+      self.0.bytes_done.load(Ordering::Relaxed)
+   }
+
+   pub fn bytes_total(&self) -> u64 {
+      self.0.bytes_total.load(Ordering::Relaxed)
+   }
+
+   /// How far along the current phase is, from 0 to 1
+   pub fn fraction(&self) -> f32 {
+      let total = self.bytes_total();
+      if total == 0 {
+         return 0.0;
+      }
+      (self.bytes_done() as f64 / total as f64).min(1.0) as f32
+   }
+
+   /// The estimated time left based on the throughput so far
+   pub fn eta(&self) -> Option<Duration> {
+      let done = self.bytes_done();
+      let total = self.bytes_total();
+      if done == 0 || total == 0 {
+         return None;
+      }
+
+      let elapsed = self.0.state.lock().unwrap().started.elapsed();
+      let remaining = total.saturating_sub(done) as f64;
+      Some(elapsed.mul_f64(remaining / done as f64))
+   }
+
+   pub fn cancel(&self) {
+      self.0.cancelled.store(true, Ordering::Relaxed);
+   }
+
+   pub fn is_cancelled(&self) -> bool {
+      self.0.cancelled.load(Ordering::Relaxed)
+   }
+
+   /// Returns [Error::Cancelled] if the job has been cancelled
+   pub fn check(&self) -> Result<(), Error> {
+      if self.is_cancelled() {
+         return Err(Error::Cancelled);
+      }
+      Ok(())
+   }
+}
+
+/// Counts the bytes read through it towards a [Progress]
+pub struct ProgressReader<'a, R: Read> {
+   inner: R,
+   progress: &'a Progress,
+}
+
+impl<'a, R: Read> ProgressReader<'a, R> {
+   pub fn new(inner: R, progress: &'a Progress) -> Self {
+      Self { inner, progress }
+   }
+
+   pub fn into_inner(self) -> R {
+      self.inner
+   }
+}
+
+impl<R: Read> Read for ProgressReader<'_, R> {
+   fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+      self.progress.check().map_err(std::io::Error::other)?;
+      let read = self.inner.read(buf)?;
+      self.progress.advance(read as u64);
+      Ok(read)
+   }
+}
+
+/// Counts the bytes written through it towards a [Progress]
+pub struct ProgressWriter<'a, W: Write> {
+   inner: W,
+   progress: &'a Progress,
+}
+
+impl<'a, W: Write> ProgressWriter<'a, W> {
+   pub fn new(inner: W, progress: &'a Progress) -> Self {
+      Self { inner, progress }
+   }
+
+   pub fn into_inner(self) -> W {
+      self.inner
+   }
+}
+
+impl<W: Write> Write for ProgressWriter<'_, W> {
+   fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+      self.progress.check().map_err(std::io::Error::other)?;
+      let written = self.inner.write(buf)?;
+      self.progress.advance(written as u64);
+      Ok(written)
+   }
+
+   fn flush(&mut self) -> std::io::Result<()> {
+      self.inner.flush()
+   }
+}
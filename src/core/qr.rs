@@ -0,0 +1,54 @@
+//! QR codes of recovery keys and shares, to show on screen or print and to read back from pictures
+
+use super::Error;
+use qrcode::{Color, QrCode, render::svg};
+use rqrr::PreparedImage;
+use std::path::Path;
+use zeroize::Zeroizing;
+
+/// The side of an exported QR code in pixels, at least
+const SVG_SIZE: u32 = 256;
+
+/// The modules of the QR code of `text` row by row, `true` for the dark ones, along with the width of a row
+pub fn modules(text: &str) -> Result<(usize, Vec<bool>), Error> {
+   let code = code(text)?;
+   let modules = code
+      .to_colors()
+      .into_iter()
+      .map(|color| color == Color::Dark)
+      .collect();
+   Ok((code.width(), modules))
+}
+
+/// The QR code of `text` as an SVG image
+pub fn to_svg(text: &str) -> Result<String, Error> {
+   Ok(code(text)?
+      .render::<svg::Color>()
+      .min_dimensions(SVG_SIZE, SVG_SIZE)
+      .build())
+}
+
+fn code(text: &str) -> Result<QrCode, Error> {
+   QrCode::new(text.as_bytes()).map_err(|e| Error::InvalidFormat(format!("Can't make a QR code: {}", e)))
+}
+
+/// The text of every QR code found in the image at `path`
+pub fn scan(path: &Path) -> Result<Vec<Zeroizing<String>>, Error> {
+   let image =
+      image::open(path).map_err(|e| Error::InvalidFormat(format!("Can't read the image {}: {}", path.display(), e)))?;
+
+   let texts: Vec<Zeroizing<String>> = PreparedImage::prepare(image.to_luma8())
+      .detect_grids()
+      .into_iter()
+      .filter_map(|grid| grid.decode().ok())
+      .map(|(_, text)| Zeroizing::new(text))
+      .collect();
+
+   if texts.is_empty() {
+      return Err(Error::InvalidFormat(format!(
+         "No QR code found in {}",
+         path.display()
+      )));
+   }
+   Ok(texts)
+}
@@ -0,0 +1,183 @@
+//! Encrypting to X25519 public keys instead of a password
+//!
+//! Every recipient gets its own [Slot::Recipient](super::stream::Slot::Recipient) holding an ephemeral public key
+//! followed by the data key sealed with XChaCha20Poly1305 under HKDF-SHA3-256 of the shared secret. Slots don't say
+//! who they are for, decrypting simply tries every one of them with the local [Identity].
+//!
+//! An identity is the X25519 secret key encrypted with credentials like any other `.ncrypt` file, so it is exactly
+//! as safe as its password.
+
+use super::{Error, Overwrite, decrypt_bytes, encrypt_bytes, output::write_file};
+use chacha20poly1305::{
+   Key, XChaCha20Poly1305, XNonce,
+   aead::{Aead, KeyInit, OsRng},
+};
+use hkdf::Hkdf;
+use ncrypt_me::{Argon2, Credentials};
+use sha3::Sha3_256;
+use std::fmt;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use x25519_dalek::{EphemeralSecret, PublicKey, StaticSecret};
+use zeroize::{Zeroize, Zeroizing};
+
+/// What public keys start with when written out
+pub const PUBLIC_KEY_PREFIX: &str = "ncryptpk";
+
+const IDENTITY_MAGIC: &[u8; 8] = b"NCRYPTID";
+const HKDF_INFO: &[u8] = b"ncrypt x25519 slot v1";
+const KEY_SIZE: usize = 32;
+const SLOT_SIZE: usize = KEY_SIZE + KEY_SIZE + 16;
+
+/// The public key of someone files can be encrypted to
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Recipient(PublicKey);
+
+impl fmt::Display for Recipient {
+   fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+      write!(f, "{}{}", PUBLIC_KEY_PREFIX, hex::encode(self.0.as_bytes()))
+   }
+}
+
+impl FromStr for Recipient {
+   type Err = Error;
+
+   fn from_str(s: &str) -> Result<Self, Self::Err> {
+      let invalid = || Error::InvalidFormat(format!("Not a valid public key: {}", s));
+
+      let key = s
+         .trim()
+         .strip_prefix(PUBLIC_KEY_PREFIX)
+         .ok_or_else(invalid)?;
+      let bytes: [u8; KEY_SIZE] = hex::decode(key)
+         .ok()
+         .and_then(|bytes| bytes.try_into().ok())
+         .ok_or_else(invalid)?;
+
+      Ok(Self(PublicKey::from(bytes)))
+   }
+}
+
+impl Recipient {
+   /// Seals `key` so only the holder of the matching [Identity] can open it
+   pub fn wrap(&self, key: &[u8; KEY_SIZE]) -> Result<Vec<u8>, Error> {
+      let ephemeral = EphemeralSecret::random_from_rng(OsRng);
+      let ephemeral_public = PublicKey::from(&ephemeral);
+
+      let shared = ephemeral.diffie_hellman(&self.0);
+      if !shared.was_contributory() {
+         return Err(Error::Encrypt("Invalid public key".to_string()));
+      }
+
+      let cipher = slot_cipher(shared.as_bytes(), &ephemeral_public, &self.0);
+      let sealed = cipher
+         .encrypt(&XNonce::default(), &key[..])
+         .map_err(|e| Error::Encrypt(e.to_string()))?;
+
+      let mut slot = Vec::with_capacity(SLOT_SIZE);
+      slot.extend_from_slice(ephemeral_public.as_bytes());
+      slot.extend_from_slice(&sealed);
+      Ok(slot)
+   }
+}
+
+/// A secret key along with its [Recipient]
+#[derive(Clone)]
+pub struct Identity {
+   secret: StaticSecret,
+}
+
+impl Identity {
+   pub fn generate() -> Self {
+      Self {
+         secret: StaticSecret::random_from_rng(OsRng),
+      }
+   }
+
+   pub fn recipient(&self) -> Recipient {
+      Recipient(PublicKey::from(&self.secret))
+   }
+
+   /// Writes the identity to `path` encrypted with `credentials`
+   ///
+   /// Returns the path actually written
+   pub fn save(
+      &self,
+      path: &Path,
+      argon2: Argon2,
+      credentials: Credentials,
+      overwrite: Overwrite,
+   ) -> Result<PathBuf, Error> {
+      let mut data = Zeroizing::new(Vec::with_capacity(IDENTITY_MAGIC.len() + KEY_SIZE));
+      data.extend_from_slice(IDENTITY_MAGIC);
+      data.extend_from_slice(self.secret.as_bytes());
+
+      let encrypted = encrypt_bytes(argon2, &data, credentials)?;
+      write_file(path, overwrite, |writer| {
+         writer.write_all(&encrypted)?;
+         Ok(())
+      })
+   }
+
+   /// Reads an identity written by [Identity::save]
+   pub fn load(path: &Path, credentials: Credentials) -> Result<Self, Error> {
+      let encrypted = std::fs::read(path).map_err(|e| Error::read(path, e))?;
+      let mut data = decrypt_bytes(encrypted, credentials)?;
+
+      let secret = data
+         .strip_prefix(IDENTITY_MAGIC)
+         .and_then(|secret| <[u8; KEY_SIZE]>::try_from(secret).ok());
+      data.zeroize();
+
+      let Some(mut secret) = secret else {
+         return Err(Error::InvalidFormat(format!(
+            "{} is not an identity",
+            path.display()
+         )));
+      };
+
+      let identity = Self {
+         secret: StaticSecret::from(secret),
+      };
+      secret.zeroize();
+      Ok(identity)
+   }
+
+   /// Opens a slot made by [Recipient::wrap], `None` if it is meant for someone else
+   pub fn unwrap(&self, slot: &[u8]) -> Option<Zeroizing<[u8; KEY_SIZE]>> {
+      if slot.len() != SLOT_SIZE {
+         return None;
+      }
+
+      let ephemeral_public: [u8; KEY_SIZE] = slot[..KEY_SIZE].try_into().ok()?;
+      let ephemeral_public = PublicKey::from(ephemeral_public);
+
+      let shared = self.secret.diffie_hellman(&ephemeral_public);
+      if !shared.was_contributory() {
+         return None;
+      }
+
+      let cipher = slot_cipher(shared.as_bytes(), &ephemeral_public, &self.recipient().0);
+      let mut opened = cipher.decrypt(&XNonce::default(), &slot[KEY_SIZE..]).ok()?;
+
+      let mut key = Zeroizing::new([0u8; KEY_SIZE]);
+      key.copy_from_slice(&opened);
+      opened.zeroize();
+      Some(key)
+   }
+}
+
+/// Every slot has its own ephemeral key, so the wrapping key is never reused and a fixed nonce is fine
+fn slot_cipher(shared: &[u8; KEY_SIZE], ephemeral: &PublicKey, recipient: &PublicKey) -> XChaCha20Poly1305 {
+   let mut salt = [0u8; KEY_SIZE * 2];
+   salt[..KEY_SIZE].copy_from_slice(ephemeral.as_bytes());
+   salt[KEY_SIZE..].copy_from_slice(recipient.as_bytes());
+
+   let mut wrapping_key = Zeroizing::new([0u8; KEY_SIZE]);
+   Hkdf::<Sha3_256>::new(Some(&salt), shared)
+      .expand(HKDF_INFO, &mut wrapping_key[..])
+      .expect("32 bytes is a valid HKDF output length");
+
+   XChaCha20Poly1305::new(Key::from_slice(&wrapping_key[..]))
+}
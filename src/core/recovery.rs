@@ -0,0 +1,154 @@
+//! Random keys that open a file without any password, for when the credentials are lost
+//!
+//! A [RecoveryKey] wraps the data key in its own [Slot::Recovery](super::stream::Slot::Recovery): a random nonce
+//! followed by the data key sealed with XChaCha20Poly1305 under HKDF-SHA3-256 of the recovery key. The key is
+//! random, so there is nothing for Argon2 to stretch. It can be split into [shares](super::shamir) for trustees,
+//! or written down as 24 [BIP39](bip39) words whose last one carries a checksum and printed on a
+//! [sheet](RecoveryKey::sheet).
+
+use super::{Error, qr};
+use bip39::Mnemonic;
+use chacha20poly1305::{
+   Key, XChaCha20Poly1305, XNonce,
+   aead::{Aead, KeyInit, OsRng, rand_core::RngCore},
+};
+use hkdf::Hkdf;
+use sha3::Sha3_256;
+use zeroize::{Zeroize, Zeroizing};
+
+pub const KEY_SIZE: usize = 32;
+
+const HKDF_INFO: &[u8] = b"ncrypt recovery slot v1";
+const NONCE_SIZE: usize = 24;
+const SLOT_SIZE: usize = NONCE_SIZE + KEY_SIZE + 16;
+
+/// A random key kept apart from the file by its owner
+#[derive(Clone)]
+pub struct RecoveryKey(Zeroizing<[u8; KEY_SIZE]>);
+
+impl RecoveryKey {
+   pub fn generate() -> Self {
+      let mut key = Zeroizing::new([0u8; KEY_SIZE]);
+      OsRng.fill_bytes(&mut key[..]);
+      Self(key)
+   }
+
+   pub fn from_bytes(bytes: &[u8]) -> Result<Self, Error> {
+      let mut key = Zeroizing::new([0u8; KEY_SIZE]);
+      if bytes.len() != KEY_SIZE {
+         return Err(Error::InvalidFormat("Invalid recovery key".to_string()));
+      }
+      key.copy_from_slice(bytes);
+      Ok(Self(key))
+   }
+
+   pub fn as_bytes(&self) -> &[u8; KEY_SIZE] {
+      &self.0
+   }
+
+   /// The key as 24 words separated by spaces
+   pub fn to_words(&self) -> Zeroizing<String> {
+      let mnemonic = Mnemonic::from_entropy(&self.0[..]).expect("32 bytes is a valid entropy length");
+      Zeroizing::new(mnemonic.to_string())
+   }
+
+   /// Reads the words of [RecoveryKey::to_words] back, in any case and separated by any whitespace
+   pub fn from_words(words: &str) -> Result<Self, Error> {
+      let words = Zeroizing::new(words.to_lowercase());
+      let mnemonic = Mnemonic::parse(words.as_str())
+         .map_err(|e| Error::InvalidFormat(format!("Not a valid recovery key: {}", e)))?;
+
+      let mut entropy = mnemonic.to_entropy();
+      let key = Self::from_bytes(&entropy);
+      entropy.zeroize();
+      key
+   }
+
+   /// A printable A4 page with the words and the QR code of the key, as an SVG image
+   pub fn sheet(&self) -> Result<Zeroizing<String>, Error> {
+      let words = self.to_words();
+      let (width, modules) = qr::modules(&words)?;
+
+      let mut svg = Zeroizing::new(String::new());
+      svg.push_str(
+         r#"<svg xmlns="http://www.w3.org/2000/svg" width="210mm" height="297mm" viewBox="0 0 210 297" font-family="sans-serif">"#,
+      );
+      svg.push_str(r#"<rect width="210" height="297" fill="white"/>"#);
+      svg.push_str(r#"<text x="20" y="30" font-size="8">ncrypt recovery key</text>"#);
+      svg.push_str(
+         r#"<text x="20" y="40" font-size="4">Anyone holding this sheet can decrypt the files it was made for, keep it somewhere safe.</text>"#,
+      );
+
+      // 3 columns of 8 words
+      for (i, word) in words.split(' ').enumerate() {
+         svg.push_str(&format!(
+            r#"<text x="{}" y="{}" font-size="5">{}. {}</text>"#,
+            20 + i / 8 * 60,
+            60 + i % 8 * 9,
+            i + 1,
+            word
+         ));
+      }
+
+      // The QR code with a quiet zone of 2 modules, 80 mm wide
+      let module = 80.0 / (width + 4) as f32;
+      svg.push_str(&format!(
+         r#"<g transform="translate(65 150) scale({})" fill="black" shape-rendering="crispEdges">"#,
+         module
+      ));
+      for (i, dark) in modules.iter().enumerate() {
+         if *dark {
+            svg.push_str(&format!(
+               r#"<rect x="{}" y="{}" width="1" height="1"/>"#,
+               i % width + 2,
+               i / width + 2
+            ));
+         }
+      }
+      svg.push_str("</g></svg>");
+      Ok(svg)
+   }
+
+   /// Seals `key` so this recovery key opens it
+   pub fn wrap(&self, key: &[u8; KEY_SIZE]) -> Result<Vec<u8>, Error> {
+      let mut nonce = [0u8; NONCE_SIZE];
+      OsRng.fill_bytes(&mut nonce);
+
+      let sealed = self
+         .cipher()
+         .encrypt(XNonce::from_slice(&nonce), &key[..])
+         .map_err(|e| Error::Encrypt(e.to_string()))?;
+
+      let mut slot = Vec::with_capacity(SLOT_SIZE);
+      slot.extend_from_slice(&nonce);
+      slot.extend_from_slice(&sealed);
+      Ok(slot)
+   }
+
+   /// Opens a slot made by [RecoveryKey::wrap], `None` if it was made with another key
+   pub fn unwrap(&self, slot: &[u8]) -> Option<Zeroizing<[u8; KEY_SIZE]>> {
+      if slot.len() != SLOT_SIZE {
+         return None;
+      }
+
+      let (nonce, sealed) = slot.split_at(NONCE_SIZE);
+      let mut opened = self
+         .cipher()
+         .decrypt(XNonce::from_slice(nonce), sealed)
+         .ok()?;
+
+      let mut key = Zeroizing::new([0u8; KEY_SIZE]);
+      key.copy_from_slice(&opened);
+      opened.zeroize();
+      Some(key)
+   }
+
+   fn cipher(&self) -> XChaCha20Poly1305 {
+      let mut wrapping_key = Zeroizing::new([0u8; KEY_SIZE]);
+      Hkdf::<Sha3_256>::new(None, &self.0[..])
+         .expand(HKDF_INFO, &mut wrapping_key[..])
+         .expect("32 bytes is a valid HKDF output length");
+
+      XChaCha20Poly1305::new(Key::from_slice(&wrapping_key[..]))
+   }
+}
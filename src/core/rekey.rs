@@ -0,0 +1,297 @@
+//! Changing the credentials of an encrypted file in place
+//!
+//! The plaintext never touches the disk. The file is decrypted and encrypted again under a new data key in memory,
+//! chunk by chunk, into a temporary file that atomically replaces the original. Files with more than one [Slot]
+//! only get the unlocked slot rewrapped like in [slots], as the other slots can't be wrapped anew without their
+//! secrets. Their data key stays the same, so whoever got hold of it before can still decrypt them.
+//!
+//! [scan] finds the files whose Argon2 parameters fell behind, so they can be re-keyed with
+//! [rekey_batch](super::batch::rekey_batch).
+
+use super::{
+   Error, FILE_EXTENSION, Metadata, Options, PayloadKind, age_format, check_unlock, decrypt_bytes, encrypted_info,
+   inspect, memory,
+   output::rewrite_file,
+   progress::{Phase, Progress, ProgressReader},
+   read_payload_start,
+   slots::{self, SlotInfo},
+   stream::{self, DataKey, Decryptor, Encryptor, Secret, Slot},
+   unlock_error,
+};
+use ncrypt_me::{Argon2, Credentials};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use zeroize::Zeroize;
+
+/// A file with a password weaker than the minimum [scan] was given
+#[derive(Clone, Debug)]
+pub struct WeakFile {
+   pub path: PathBuf,
+   /// The parameters of its weakest password
+   pub argon2: Argon2,
+}
+
+/// What [scan] found in a folder
+#[derive(Clone, Debug, Default)]
+pub struct ScanReport {
+   pub weak: Vec<WeakFile>,
+   /// The number of files that meet the minimum
+   pub strong: usize,
+   /// The files that couldn't be read or have no password, along with the reason
+   pub skipped: Vec<(PathBuf, String)>,
+}
+
+impl ScanReport {
+   /// A message describing the findings
+   pub fn message(&self, minimum: &Argon2) -> String {
+      let total = self.weak.len() + self.strong;
+      let mut msg = format!(
+         "{} of {} files are below {} and {} iterations",
+         self.weak.len(),
+         total,
+         memory::format_kib(minimum.m_cost as u64),
+         minimum.t_cost
+      );
+
+      for file in &self.weak {
+         let name = file
+            .path
+            .file_name()
+            .unwrap_or(file.path.as_os_str())
+            .to_string_lossy();
+         msg.push_str(&format!(
+            "\n{}: {}, {} iterations",
+            name,
+            memory::format_kib(file.argon2.m_cost as u64),
+            file.argon2.t_cost
+         ));
+      }
+
+      if !self.skipped.is_empty() {
+         msg.push_str(&format!("\n\nSkipped {} files:", self.skipped.len()));
+         for (path, reason) in &self.skipped {
+            msg.push_str(&format!("\n{}: {}", path.display(), reason));
+         }
+      }
+
+      msg
+   }
+}
+
+/// Whether `argon2` uses less memory or fewer iterations than `minimum`
+pub fn is_below(argon2: &Argon2, minimum: &Argon2) -> bool {
+   argon2.m_cost < minimum.m_cost || argon2.t_cost < minimum.t_cost
+}
+
+/// Looks through `dir` and its subfolders for `.ncrypt` files with a password below `minimum`
+///
+/// Only the headers are read, so no credentials are needed.
+pub fn scan(dir: &Path, minimum: &Argon2) -> Result<ScanReport, Error> {
+   let mut paths = Vec::new();
+   collect_encrypted(dir, &mut paths)?;
+   paths.sort();
+
+   let mut report = ScanReport::default();
+   for path in paths {
+      match weakest_password(&path) {
+         Ok(Some(argon2)) if is_below(&argon2, minimum) => report.weak.push(WeakFile { path, argon2 }),
+         Ok(Some(_)) => report.strong += 1,
+         Ok(None) => report
+            .skipped
+            .push((path, "No password to re-key".to_string())),
+         Err(e) => report.skipped.push((path, e.to_string())),
+      }
+   }
+   Ok(report)
+}
+
+/// Adds every file under `dir` whose name ends with [FILE_EXTENSION] to `paths`, symlinks are not followed
+fn collect_encrypted(dir: &Path, paths: &mut Vec<PathBuf>) -> Result<(), Error> {
+   let entries = std::fs::read_dir(dir).map_err(|e| Error::read(dir, e))?;
+
+   for entry in entries {
+      let entry = entry.map_err(|e| Error::read(dir, e))?;
+      let path = entry.path();
+      match entry.file_type() {
+         Ok(file_type) if file_type.is_dir() => collect_encrypted(&path, paths)?,
+         Ok(file_type) if file_type.is_file() && path.to_string_lossy().ends_with(FILE_EXTENSION) => paths.push(path),
+         _ => {}
+      }
+   }
+   Ok(())
+}
+
+/// The Argon2 parameters of the password slot with the least memory, `None` if there is no password at all
+pub(super) fn weakest_password(path: &Path) -> Result<Option<Argon2>, Error> {
+   let params: Vec<Argon2> = if stream::is_chunked_file(path)? {
+      slots::list(path)?
+         .into_iter()
+         .filter_map(|slot| match slot {
+            SlotInfo::Password(argon2) => argon2,
+            _ => None,
+         })
+         .collect()
+   } else {
+      inspect(path)?.argon2.into_iter().collect()
+   };
+
+   Ok(params
+      .into_iter()
+      .min_by_key(|argon2| (argon2.m_cost, argon2.t_cost)))
+}
+
+/// Makes the file at `file_path` open with `new` instead of `old`
+///
+/// `argon2` defaults to the parameters of the old password. [Options::keyfile] says whether `old` has keyfiles
+/// mixed in and `keyfile` whether `new` does. Other key slots of the file are kept, but whether keyfiles are used
+/// is authenticated with every chunk, so it can only change on files with a single slot. Only files with a single
+/// slot get a new data key.
+pub fn change_credentials(
+   file_path: &Path,
+   old: Credentials,
+   argon2: Option<Argon2>,
+   new: Credentials,
+   keyfile: bool,
+   options: &Options,
+   progress: &Progress,
+) -> Result<(), Error> {
+   if let Some(argon2) = &argon2
+      && !options.ignore_memory_limit
+   {
+      memory::check(argon2)?;
+   }
+
+   if age_format::is_age_file(file_path)? {
+      return Err(Error::InvalidFormat(
+         "This is an age file, change its passphrase with age".to_string(),
+      ));
+   }
+
+   if !stream::is_chunked_file(file_path)? {
+      return change_single_shot(file_path, old, argon2, new, keyfile, options, progress);
+   }
+
+   let (header, reader) = slots::read_header(file_path)?;
+   let secret = Secret::Credentials(old);
+
+   if header.slots.len() > 1 {
+      if header.needs_keyfile() != keyfile {
+         return Err(Error::Keyfile(
+            "The other key slots of this file would stop working, remove them before changing whether keyfiles are used"
+               .to_string(),
+         ));
+      }
+
+      drop(reader);
+      return slots::edit(file_path, &secret, options, progress, |key, _| {
+         let argon2 = match argon2 {
+            Some(argon2) => argon2,
+            None => old_argon2(key.opened())?,
+         };
+
+         progress.set_phase(Phase::DerivingKey);
+         key.replace_password(argon2, new)
+      });
+   }
+
+   check_unlock(&header, &secret, options, false)?;
+
+   progress.set_phase(Phase::DerivingKey);
+   let key = DataKey::unwrap(&header.slots, &secret).map_err(|e| unlock_error(e, &header, &secret))?;
+   let argon2 = match argon2 {
+      Some(argon2) => argon2,
+      None => old_argon2(key.opened())?,
+   };
+   let new_key = new_key(argon2, new, keyfile)?;
+   progress.check()?;
+
+   let total = std::fs::metadata(file_path).map(|m| m.len()).unwrap_or(0);
+   progress.start(
+      Phase::Encrypting,
+      total.saturating_sub(header.size() as u64),
+   );
+   let reader = ProgressReader::new(reader, progress);
+   let mut reader = Decryptor::new(&key, header, reader)?;
+   let (kind, metadata) = read_payload_start(&mut reader)?;
+
+   // The closure owns the reader so the file is closed before it gets replaced
+   rewrite_file(file_path, move |writer| {
+      let mut encryptor = Encryptor::new(&new_key, writer)?;
+      encryptor.write_all(&[kind as u8])?;
+      metadata.write_to(&mut encryptor)?;
+      std::io::copy(&mut reader, &mut encryptor)?;
+
+      progress.set_phase(Phase::Writing);
+      encryptor.finish()?;
+      Ok(())
+   })
+}
+
+/// The old single shot format has no room for keyfiles or [Metadata], so the file moves to the chunked format
+fn change_single_shot(
+   file_path: &Path,
+   old: Credentials,
+   argon2: Option<Argon2>,
+   new: Credentials,
+   keyfile: bool,
+   options: &Options,
+   progress: &Progress,
+) -> Result<(), Error> {
+   if options.keyfile {
+      return Err(Error::Keyfile(
+         "Files in the old single shot format can't use keyfiles".to_string(),
+      ));
+   }
+
+   progress.set_phase(Phase::Reading);
+   let encrypted_data = std::fs::read(file_path).map_err(|e| Error::read(file_path, e))?;
+   progress.check()?;
+
+   if !options.ignore_memory_limit {
+      memory::check_encrypted(&encrypted_data)?;
+   }
+
+   let argon2 = match argon2 {
+      Some(argon2) => argon2,
+      None => encrypted_info(&encrypted_data)?.0.argon2,
+   };
+
+   progress.set_phase(Phase::Decrypting);
+   let mut decrypted_data = decrypt_bytes(encrypted_data, old)?;
+
+   progress.set_phase(Phase::DerivingKey);
+   let res = new_key(argon2, new, keyfile).and_then(|key| {
+      progress.check()?;
+      progress.set_phase(Phase::Writing);
+      rewrite_file(file_path, |writer| {
+         let mut encryptor = Encryptor::new(&key, writer)?;
+         encryptor.write_all(&[PayloadKind::File as u8])?;
+         Metadata::default().write_to(&mut encryptor)?;
+         encryptor.write_all(&decrypted_data)?;
+         encryptor.finish()?;
+         Ok(())
+      })
+   });
+   decrypted_data.zeroize();
+
+   res
+}
+
+/// The Argon2 parameters of the unlocked password slot, so they carry over to the new password
+fn old_argon2(slot: Option<&Slot>) -> Result<Argon2, Error> {
+   match slot {
+      Some(Slot::Password(wrapped_key)) => Ok(encrypted_info(wrapped_key)?.0.argon2),
+      _ => Err(Error::Decrypt(
+         "The file was not unlocked with a password".to_string(),
+      )),
+   }
+}
+
+/// A fresh [DataKey] for `credentials`, flagged as using keyfiles if `keyfile` is set
+fn new_key(argon2: Argon2, credentials: Credentials, keyfile: bool) -> Result<DataKey, Error> {
+   let mut key = DataKey::generate(argon2, credentials)?;
+   if keyfile {
+      key.flags |= stream::FLAG_KEYFILE;
+   }
+   Ok(key)
+}
@@ -0,0 +1,203 @@
+//! Splitting a [RecoveryKey] among trustees so that any `threshold` of them can recover it together
+//!
+//! Shares are written as `ncryptshare-<threshold>-<set>-<hex>`. The set is a random id shared by the shares of one
+//! split, so shares of different keys are told apart. The hex holds the x coordinate followed by one point per key
+//! byte of Shamir's scheme over GF(256). The coefficients are drawn uniformly, zero included, so fewer than
+//! `threshold` shares say nothing about the key.
+
+use super::{Error, recovery::RecoveryKey};
+use chacha20poly1305::aead::{OsRng, rand_core::RngCore};
+use std::fmt;
+use std::str::FromStr;
+use zeroize::Zeroizing;
+
+/// What shares start with when written out
+pub const SHARE_PREFIX: &str = "ncryptshare-";
+
+const SET_SIZE: usize = 8;
+
+/// One piece of a split [RecoveryKey]
+#[derive(Clone, PartialEq, Eq)]
+pub struct Share {
+   threshold: u8,
+   set: [u8; SET_SIZE],
+   data: Zeroizing<Vec<u8>>,
+}
+
+impl Share {
+   /// How many shares of the set it takes to recover the key
+   pub fn threshold(&self) -> u8 {
+      self.threshold
+   }
+
+   /// Which share of the set this is, numbered from 1
+   pub fn index(&self) -> u8 {
+      self.data[0]
+   }
+}
+
+impl fmt::Display for Share {
+   fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+      write!(
+         f,
+         "{}{}-{}-{}",
+         SHARE_PREFIX,
+         self.threshold,
+         hex::encode(self.set),
+         hex::encode(&self.data[..])
+      )
+   }
+}
+
+impl FromStr for Share {
+   type Err = Error;
+
+   fn from_str(s: &str) -> Result<Self, Self::Err> {
+      let invalid = || Error::InvalidFormat("Not a valid share".to_string());
+
+      let mut parts = s
+         .trim()
+         .strip_prefix(SHARE_PREFIX)
+         .ok_or_else(invalid)?
+         .split('-');
+      let (Some(threshold), Some(set), Some(data), None) = (parts.next(), parts.next(), parts.next(), parts.next())
+      else {
+         return Err(invalid());
+      };
+
+      let threshold: u8 = threshold.parse().map_err(|_| invalid())?;
+      let mut set_bytes = [0u8; SET_SIZE];
+      hex::decode_to_slice(set, &mut set_bytes).map_err(|_| invalid())?;
+      let data = Zeroizing::new(hex::decode(data).map_err(|_| invalid())?);
+      if threshold < 2 || data.len() < 2 || data[0] == 0 {
+         return Err(invalid());
+      }
+
+      Ok(Self {
+         threshold,
+         set: set_bytes,
+         data,
+      })
+   }
+}
+
+/// Splits `key` into `count` shares, any `threshold` of which recover it
+pub fn split(key: &RecoveryKey, threshold: u8, count: u8) -> Result<Vec<Share>, Error> {
+   if threshold < 2 || threshold > count {
+      return Err(Error::Encrypt(format!(
+         "Can't split a key into {} shares with {} needed to recover it, at least 2 must be needed and no more than there are",
+         count, threshold
+      )));
+   }
+
+   let mut set = [0u8; SET_SIZE];
+   OsRng.fill_bytes(&mut set);
+
+   // The coefficients above the constant term of the polynomial of every key byte
+   let secret = key.as_bytes();
+   let degree = threshold as usize - 1;
+   let mut coefficients = Zeroizing::new(vec![0u8; secret.len() * degree]);
+   OsRng.fill_bytes(&mut coefficients);
+
+   let shares = (1..=count)
+      .map(|x| {
+         let mut data = Zeroizing::new(Vec::with_capacity(1 + secret.len()));
+         data.push(x);
+         for (byte, coefficients) in secret.iter().zip(coefficients.chunks(degree)) {
+            let y = coefficients.iter().rev().fold(0, |y, c| mul(y, x) ^ c);
+            data.push(mul(y, x) ^ byte);
+         }
+
+         Share {
+            threshold,
+            set,
+            data,
+         }
+      })
+      .collect();
+   Ok(shares)
+}
+
+/// Recovers the key from at least [Share::threshold] distinct shares of the same set
+pub fn combine(shares: &[Share]) -> Result<RecoveryKey, Error> {
+   let Some(first) = shares.first() else {
+      return Err(Error::Decrypt("No shares given".to_string()));
+   };
+   let threshold = first.threshold;
+
+   let same_set = shares
+      .iter()
+      .all(|share| share.threshold == threshold && share.set == first.set && share.data.len() == first.data.len());
+   if !same_set {
+      return Err(Error::Decrypt(
+         "The shares belong to different sets".to_string(),
+      ));
+   }
+
+   let mut unique: Vec<&Share> = Vec::new();
+   for share in shares {
+      if !unique.iter().any(|other| other.index() == share.index()) {
+         unique.push(share);
+      }
+   }
+
+   if unique.len() < threshold as usize {
+      return Err(Error::Decrypt(format!(
+         "{} of the {} shares needed were given",
+         unique.len(),
+         threshold
+      )));
+   }
+   unique.truncate(threshold as usize);
+
+   // The Lagrange basis polynomials at x = 0, subtraction is xor in GF(256)
+   let basis: Vec<u8> = unique
+      .iter()
+      .map(|share| {
+         unique
+            .iter()
+            .filter(|other| other.index() != share.index())
+            .fold(1, |product, other| {
+               mul(
+                  product,
+                  mul(other.index(), inv(other.index() ^ share.index())),
+               )
+            })
+      })
+      .collect();
+
+   let mut secret = Zeroizing::new(vec![0u8; first.data.len() - 1]);
+   for (share, basis) in unique.iter().zip(&basis) {
+      for (byte, y) in secret.iter_mut().zip(&share.data[1..]) {
+         *byte ^= mul(*y, *basis);
+      }
+   }
+
+   RecoveryKey::from_bytes(&secret)
+}
+
+/// Multiplication in GF(256) modulo x^8 + x^4 + x^3 + x + 1, without branching on the operands
+fn mul(mut a: u8, mut b: u8) -> u8 {
+   let mut product = 0;
+   for _ in 0..8 {
+      product ^= a & (b & 1).wrapping_neg();
+      a = (a << 1) ^ (0x1b & (a >> 7).wrapping_neg());
+      b >>= 1;
+   }
+   product
+}
+
+/// The multiplicative inverse in GF(256), `a` to the power of 254
+fn inv(a: u8) -> u8 {
+   let mut result = 1;
+   let mut power = a;
+   let mut exponent = 254u8;
+   while exponent > 0 {
+      if exponent & 1 == 1 {
+         result = mul(result, power);
+      }
+      power = mul(power, power);
+      exponent >>= 1;
+   }
+   result
+}
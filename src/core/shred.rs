@@ -0,0 +1,82 @@
+//! Overwriting and removing plaintext originals
+//!
+//! Files are overwritten once with random data and synced to disk before being unlinked. On SSDs (wear leveling),
+//! copy on write filesystems like Btrfs, ZFS or APFS, snapshots and backups the old data can survive anyway,
+//! so this raises the bar against casual recovery but is no substitute for full disk encryption.
+
+use super::{Error, progress::Progress};
+use chacha20poly1305::aead::{OsRng, rand_core::RngCore};
+use std::fs::File;
+use std::io::Write;
+use std::path::Path;
+
+/// A short warning to show wherever shredding is offered
+pub const CAVEAT: &str = "On SSDs and copy on write filesystems (Btrfs, ZFS, APFS) or with snapshots and backups \
+   the original data may still be recoverable";
+
+const BUFFER_SIZE: usize = 64 * 1024;
+
+/// Refuses originals behind a symlink, removing the link would leave the plaintext target in place
+pub fn check_original(path: &Path) -> Result<(), Error> {
+   let metadata = std::fs::symlink_metadata(path).map_err(|e| Error::read(path, e))?;
+   if metadata.is_symlink() {
+      return Err(Error::shred(
+         path,
+         std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            "This is a symlink, select its target to remove the original",
+         ),
+      ));
+   }
+   Ok(())
+}
+
+/// Overwrites and removes the file or directory at `path`
+///
+/// `path` itself must not be a symlink, see [check_original]. Symlinks inside a directory are stored as links by
+/// the [archive](super::archive) and removed without touching their target.
+///
+/// Once started this is not interrupted by a cancel, a half overwritten original is of no use to anyone.
+pub fn shred(path: &Path, progress: &Progress) -> Result<(), Error> {
+   check_original(path)?;
+   remove(path, progress)
+}
+
+fn remove(path: &Path, progress: &Progress) -> Result<(), Error> {
+   let metadata = std::fs::symlink_metadata(path).map_err(|e| Error::read(path, e))?;
+
+   if metadata.is_dir() {
+      let entries = std::fs::read_dir(path).map_err(|e| Error::read(path, e))?;
+      for entry in entries {
+         let entry = entry.map_err(|e| Error::read(path, e))?;
+         remove(&entry.path(), progress)?;
+      }
+      return std::fs::remove_dir(path).map_err(|e| Error::shred(path, e));
+   }
+
+   if metadata.is_file() {
+      overwrite(path, metadata.len(), progress)?;
+   }
+   std::fs::remove_file(path).map_err(|e| Error::shred(path, e))
+}
+
+fn overwrite(path: &Path, len: u64, progress: &Progress) -> Result<(), Error> {
+   let mut file = File::options()
+      .write(true)
+      .open(path)
+      .map_err(|e| Error::shred(path, e))?;
+
+   let mut buffer = vec![0u8; BUFFER_SIZE];
+   let mut left = len;
+   while left > 0 {
+      let n = left.min(BUFFER_SIZE as u64) as usize;
+      OsRng.fill_bytes(&mut buffer[..n]);
+      file
+         .write_all(&buffer[..n])
+         .map_err(|e| Error::shred(path, e))?;
+      progress.advance(n as u64);
+      left -= n as u64;
+   }
+
+   file.sync_all().map_err(|e| Error::shred(path, e))
+}
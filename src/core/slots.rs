@@ -0,0 +1,164 @@
+//! Adding and removing ways to open a file without re-encrypting it
+//!
+//! Every [Slot] wraps the same data key, so only the header changes and the chunks are copied over as they are.
+//! The file is replaced atomically through [rewrite_file], keeping its permissions and modification time.
+
+use super::{
+   Error, Options, check_unlock, encrypted_info, memory,
+   output::rewrite_file,
+   progress::{Phase, Progress},
+   recipient::Recipient,
+   stream::{self, DataKey, Header, Secret, Slot},
+   unlock_error,
+};
+use ncrypt_me::{Argon2, Credentials};
+use std::fmt;
+use std::fs::File;
+use std::io::{BufReader, Read};
+use std::path::Path;
+
+/// What [list] tells about a slot
+#[derive(Clone, Debug)]
+pub enum SlotInfo {
+   /// Holds the Argon2 parameters, if they can be read
+   Password(Option<Argon2>),
+   /// For a [recipient](super::recipient)
+   Recipient,
+   /// For a [recovery key](super::recovery) or the [shares](super::shamir) it was split into
+   Recovery,
+}
+
+impl fmt::Display for SlotInfo {
+   fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+      match self {
+         Self::Password(Some(argon2)) => write!(
+            f,
+            "Password ({}, {} iterations)",
+            memory::format_kib(argon2.m_cost as u64),
+            argon2.t_cost
+         ),
+         Self::Password(None) => write!(f, "Password"),
+         Self::Recipient => write!(f, "Recipient"),
+         Self::Recovery => write!(f, "Recovery key"),
+      }
+   }
+}
+
+/// The slots of the chunked file at `file_path`, no credentials needed
+pub fn list(file_path: &Path) -> Result<Vec<SlotInfo>, Error> {
+   let (header, _) = read_header(file_path)?;
+
+   Ok(header
+      .slots
+      .iter()
+      .map(|slot| match slot {
+         Slot::Password(wrapped_key) => SlotInfo::Password(
+            encrypted_info(wrapped_key)
+               .ok()
+               .map(|(info, _)| info.argon2),
+         ),
+         Slot::Recipient(_) => SlotInfo::Recipient,
+         Slot::Recovery(_) => SlotInfo::Recovery,
+      })
+      .collect())
+}
+
+/// Lets `credentials` open the file at `file_path` as well, `secret` is anything that opens it already
+///
+/// All passwords of a file either use keyfiles or not, [Options::keyfile] says which.
+pub fn add_password(
+   file_path: &Path,
+   secret: &Secret,
+   argon2: Argon2,
+   credentials: Credentials,
+   options: &Options,
+   progress: &Progress,
+) -> Result<(), Error> {
+   if !options.ignore_memory_limit {
+      memory::check(&argon2)?;
+   }
+
+   edit(file_path, secret, options, progress, |key, header| {
+      if header.needs_keyfile() != options.keyfile {
+         return Err(Error::Keyfile(if header.needs_keyfile() {
+            "The passwords of this file use keyfiles, select some for the new password too".to_string()
+         } else {
+            "The passwords of this file don't use keyfiles, deselect them for the new password".to_string()
+         }));
+      }
+
+      progress.set_phase(Phase::DerivingKey);
+      key.add_password(argon2, credentials)
+   })
+}
+
+/// Lets the identity behind `recipient` open the file at `file_path` as well
+pub fn add_recipient(
+   file_path: &Path,
+   secret: &Secret,
+   recipient: &Recipient,
+   options: &Options,
+   progress: &Progress,
+) -> Result<(), Error> {
+   edit(file_path, secret, options, progress, |key, _| {
+      key.add_recipient(recipient)
+   })
+}
+
+/// Removes the slot at `index` from the file at `file_path`
+///
+/// `secret` has to open one of the other slots, so the file can't be locked for good by accident.
+pub fn remove(
+   file_path: &Path,
+   index: usize,
+   secret: &Secret,
+   options: &Options,
+   progress: &Progress,
+) -> Result<(), Error> {
+   edit(file_path, secret, options, progress, |key, _| {
+      key.remove_slot(index).map(|_| ())
+   })
+}
+
+/// Unwraps the key of the file with `secret`, lets `change` edit its slots and writes them back
+pub(super) fn edit(
+   file_path: &Path,
+   secret: &Secret,
+   options: &Options,
+   progress: &Progress,
+   change: impl FnOnce(&mut DataKey, &Header) -> Result<(), Error>,
+) -> Result<(), Error> {
+   let (mut header, mut reader) = read_header(file_path)?;
+
+   check_unlock(&header, secret, options, false)?;
+
+   progress.set_phase(Phase::DerivingKey);
+   let mut key = DataKey::unwrap(&header.slots, secret).map_err(|e| unlock_error(e, &header, secret))?;
+   progress.check()?;
+
+   change(&mut key, &header)?;
+   progress.check()?;
+   header.slots = key.slots().to_vec();
+
+   // The closure owns the reader so the file is closed before it gets replaced
+   progress.set_phase(Phase::Writing);
+   rewrite_file(file_path, move |writer| {
+      header.write_to(writer)?;
+      std::io::copy(&mut reader, writer)?;
+      Ok(())
+   })
+}
+
+/// Reads the header of the chunked file at `file_path`, the reader continues with the first chunk
+pub(super) fn read_header(file_path: &Path) -> Result<(Header, impl Read), Error> {
+   if !stream::is_chunked_file(file_path)? {
+      return Err(Error::InvalidFormat(
+         "Only files in the chunked format have key slots".to_string(),
+      ));
+   }
+
+   let file = File::open(file_path).map_err(|e| Error::read(file_path, e))?;
+   let mut reader = BufReader::new(file);
+   let header = Header::read_from(&mut reader)?;
+   Ok((header, reader))
+}
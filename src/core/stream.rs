@@ -0,0 +1,758 @@
+//! Chunked encryption for files of any size
+//!
+//! A random data key encrypts the file in fixed size chunks with XChaCha20Poly1305, and that key is
+//! itself encrypted by [ncrypt_me] under the Argon2 derived key, or sealed to [recipients](super::recipient) or a
+//! [recovery key](super::recovery), so memory use stays bounded by the chunk size.
+//!
+//! ## Format
+//!
+//! | Field            | Size                  |
+//! |------------------|-----------------------|
+//! | Magic            | 8 bytes               |
+//! | Version          | 1 byte                |
+//! | Flags            | 1 byte                |
+//! | Chunk size       | 4 bytes (LE)          |
+//! | Nonce prefix     | 19 bytes              |
+//! | Slot count       | 1 byte                |
+//! | Slots            | See below             |
+//! | Chunks           | Until EOF             |
+//!
+//! Every slot holds the data key wrapped one way:
+//!
+//! | Field            | Size                  |
+//! |------------------|-----------------------|
+//! | Kind             | 1 byte                |
+//! | Wrapped key len  | 4 bytes (LE)          |
+//! | Wrapped key      | Wrapped key len bytes |
+//!
+//! Every chunk holds `chunk size` bytes of plaintext plus a 16 byte tag, except the last one which is always
+//! shorter (it may hold no plaintext at all). The nonce of a chunk is the prefix followed by a 4 byte big endian
+//! counter and a byte set to 1 only for the last chunk, so reordered, dropped or truncated chunks fail to decrypt.
+//! The fixed part of the header is authenticated with every chunk.
+//!
+//! The first plaintext byte is the [PayloadKind](super::PayloadKind) of what follows, followed by the
+//! [Metadata](super::Metadata) of the original file.
+
+use super::{
+   Error,
+   age_format::AgeSecret,
+   decrypt_bytes, encrypt_bytes,
+   recipient::{Identity, Recipient},
+   recovery::RecoveryKey,
+};
+use chacha20poly1305::{
+   Key, XChaCha20Poly1305, XNonce,
+   aead::{AeadInPlace, KeyInit, OsRng, rand_core::RngCore},
+};
+use ncrypt_me::{Argon2, Credentials};
+use std::io::{ErrorKind, Read, Write};
+use std::path::Path;
+use zeroize::{Zeroize, Zeroizing};
+
+pub const MAGIC: &[u8; 8] = b"NCRYPTST";
+pub const VERSION: u8 = 1;
+
+/// Plaintext bytes in every chunk but the last
+pub const CHUNK_SIZE: usize = 1024 * 1024;
+
+const MAX_CHUNK_SIZE: usize = 16 * 1024 * 1024;
+const MAX_WRAPPED_KEY_SIZE: usize = 64 * 1024;
+const NONCE_PREFIX_SIZE: usize = 19;
+const TAG_SIZE: usize = 16;
+const KEY_SIZE: usize = 32;
+const MAX_SLOTS: usize = 64;
+
+/// Header flag set when the key was derived with [keyfiles](super::keyfile) mixed in
+pub const FLAG_KEYFILE: u8 = 1;
+
+/// A copy of the data key wrapped for one way of opening the file
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Slot {
+   /// Encrypted by [ncrypt_me] under a key derived from credentials
+   Password(Vec<u8>),
+   /// Sealed to a [Recipient] public key
+   Recipient(Vec<u8>),
+   /// Sealed under a [RecoveryKey]
+   Recovery(Vec<u8>),
+}
+
+impl Slot {
+   fn kind(&self) -> u8 {
+      match self {
+         Self::Password(_) => 0,
+         Self::Recipient(_) => 1,
+         Self::Recovery(_) => 2,
+      }
+   }
+
+   pub fn data(&self) -> &[u8] {
+      match self {
+         Self::Password(data) | Self::Recipient(data) | Self::Recovery(data) => data,
+      }
+   }
+
+   fn from_kind(kind: u8, data: Vec<u8>) -> Result<Self, Error> {
+      match kind {
+         0 => Ok(Self::Password(data)),
+         1 => Ok(Self::Recipient(data)),
+         2 => Ok(Self::Recovery(data)),
+         _ => Err(Error::InvalidFormat(format!(
+            "Unknown key slot kind {}",
+            kind
+         ))),
+      }
+   }
+}
+
+/// The header at the start of every chunked file
+#[derive(Clone)]
+pub struct Header {
+   pub version: u8,
+   pub flags: u8,
+   pub chunk_size: u32,
+   pub nonce_prefix: [u8; NONCE_PREFIX_SIZE],
+   /// The data key wrapped once per way of opening the file
+   pub slots: Vec<Slot>,
+}
+
+impl Header {
+   fn new(slots: Vec<Slot>, flags: u8) -> Self {
+      let mut nonce_prefix = [0u8; NONCE_PREFIX_SIZE];
+      OsRng.fill_bytes(&mut nonce_prefix);
+
+      Self {
+         version: VERSION,
+         flags,
+         chunk_size: CHUNK_SIZE as u32,
+         nonce_prefix,
+         slots,
+      }
+   }
+
+   /// Whether decrypting needs the keyfiles the file was encrypted with
+   pub fn needs_keyfile(&self) -> bool {
+      self.flags & FLAG_KEYFILE != 0
+   }
+
+   /// The [Slot::Password] slots
+   pub fn password_slots(&self) -> impl Iterator<Item = &[u8]> {
+      self.slots.iter().filter_map(|slot| match slot {
+         Slot::Password(data) => Some(data.as_slice()),
+         _ => None,
+      })
+   }
+
+   /// The number of [Slot::Recipient] slots
+   pub fn recipient_count(&self) -> usize {
+      self
+         .slots
+         .iter()
+         .filter(|slot| matches!(slot, Slot::Recipient(_)))
+         .count()
+   }
+
+   /// The number of [Slot::Recovery] slots
+   pub fn recovery_count(&self) -> usize {
+      self
+         .slots
+         .iter()
+         .filter(|slot| matches!(slot, Slot::Recovery(_)))
+         .count()
+   }
+
+   /// The fixed part of the header, authenticated with every chunk
+   ///
+   /// The slots are left out so they can change without touching the chunks.
+   fn aad(&self) -> Vec<u8> {
+      let mut aad = Vec::with_capacity(MAGIC.len() + 2 + 4 + NONCE_PREFIX_SIZE);
+      aad.extend_from_slice(MAGIC);
+      aad.push(self.version);
+      aad.push(self.flags);
+      aad.extend_from_slice(&self.chunk_size.to_le_bytes());
+      aad.extend_from_slice(&self.nonce_prefix);
+      aad
+   }
+
+   /// The size of the header on disk
+   pub fn size(&self) -> usize {
+      let slots: usize = self
+         .slots
+         .iter()
+         .map(|slot| 1 + 4 + slot.data().len())
+         .sum();
+      self.aad().len() + 1 + slots
+   }
+
+   pub fn write_to<W: Write>(&self, writer: &mut W) -> Result<(), Error> {
+      if self.slots.is_empty() || self.slots.len() > MAX_SLOTS {
+         return Err(Error::InvalidFormat(format!(
+            "A file holds 1 to {} key slots",
+            MAX_SLOTS
+         )));
+      }
+
+      writer.write_all(&self.aad())?;
+      writer.write_all(&[self.slots.len() as u8])?;
+      for slot in &self.slots {
+         writer.write_all(&[slot.kind()])?;
+         writer.write_all(&(slot.data().len() as u32).to_le_bytes())?;
+         writer.write_all(slot.data())?;
+      }
+      Ok(())
+   }
+
+   pub fn read_from<R: Read>(reader: &mut R) -> Result<Self, Error> {
+      let mut magic = [0u8; 8];
+      reader.read_exact(&mut magic)?;
+      if &magic != MAGIC {
+         return Err(Error::InvalidFormat(
+            "Not a chunked nCrypt file".to_string(),
+         ));
+      }
+
+      let mut version = [0u8; 1];
+      reader.read_exact(&mut version)?;
+      let version = version[0];
+      if version != VERSION {
+         return Err(Error::InvalidFormat(format!(
+            "Unsupported version {}",
+            version
+         )));
+      }
+
+      let mut flags = [0u8; 1];
+      reader.read_exact(&mut flags)?;
+      let flags = flags[0];
+      if flags & !FLAG_KEYFILE != 0 {
+         return Err(Error::InvalidFormat(format!(
+            "Unknown flags {:#04x}",
+            flags
+         )));
+      }
+
+      let chunk_size = read_u32(reader)?;
+      if chunk_size == 0 || chunk_size as usize > MAX_CHUNK_SIZE {
+         return Err(Error::InvalidFormat(format!(
+            "Invalid chunk size {}",
+            chunk_size
+         )));
+      }
+
+      let mut nonce_prefix = [0u8; NONCE_PREFIX_SIZE];
+      reader.read_exact(&mut nonce_prefix)?;
+
+      let mut count = [0u8; 1];
+      reader.read_exact(&mut count)?;
+      let count = count[0] as usize;
+      if count == 0 || count > MAX_SLOTS {
+         return Err(Error::InvalidFormat(format!(
+            "Invalid key slot count {}",
+            count
+         )));
+      }
+
+      let mut slots = Vec::with_capacity(count);
+      for _ in 0..count {
+         let mut kind = [0u8; 1];
+         reader.read_exact(&mut kind)?;
+         slots.push(Slot::from_kind(kind[0], read_slot_data(reader)?)?);
+      }
+
+      Ok(Self {
+         version,
+         flags,
+         chunk_size,
+         nonce_prefix,
+         slots,
+      })
+   }
+}
+
+/// What opens a file, either credentials for a password slot, an identity for a recipient slot or a recovery key
+#[derive(Clone)]
+pub enum Secret {
+   Credentials(Credentials),
+   Identity(Identity),
+   Recovery(RecoveryKey),
+   /// Only opens files in the [age format](super::age_format)
+   Age(AgeSecret),
+}
+
+impl From<Credentials> for Secret {
+   fn from(credentials: Credentials) -> Self {
+      Self::Credentials(credentials)
+   }
+}
+
+impl From<AgeSecret> for Secret {
+   fn from(secret: AgeSecret) -> Self {
+      Self::Age(secret)
+   }
+}
+
+impl From<Identity> for Secret {
+   fn from(identity: Identity) -> Self {
+      Self::Identity(identity)
+   }
+}
+
+impl From<RecoveryKey> for Secret {
+   fn from(key: RecoveryKey) -> Self {
+      Self::Recovery(key)
+   }
+}
+
+/// The random key the chunks are encrypted with, along with the [Slot]s stored in the [Header]
+pub struct DataKey {
+   key: Zeroizing<[u8; KEY_SIZE]>,
+   slots: Vec<Slot>,
+   /// The slot the key was unwrapped from
+   opened: Option<usize>,
+   /// The [Header] flags files encrypted with this key get
+   pub flags: u8,
+}
+
+impl DataKey {
+   fn random() -> Self {
+      let mut key = Zeroizing::new([0u8; KEY_SIZE]);
+      OsRng.fill_bytes(&mut key[..]);
+
+      Self {
+         key,
+         slots: Vec::new(),
+         opened: None,
+         flags: 0,
+      }
+   }
+
+   /// Generates a new key and wraps it under the key derived from `credentials` with `argon2`
+   ///
+   /// This is where all the Argon2 work happens, the same key can then encrypt any number of files.
+   pub fn generate(argon2: Argon2, credentials: Credentials) -> Result<Self, Error> {
+      let mut key = Self::random();
+      key.add_password(argon2, credentials)?;
+      Ok(key)
+   }
+
+   /// Generates a new key that only the holders of the identities behind `recipients` can unwrap
+   pub fn for_recipients(recipients: &[Recipient]) -> Result<Self, Error> {
+      if recipients.is_empty() {
+         return Err(Error::Encrypt("No recipients given".to_string()));
+      }
+
+      let mut key = Self::random();
+      for recipient in recipients {
+         key.add_recipient(recipient)?;
+      }
+      Ok(key)
+   }
+
+   /// Adds a slot that `credentials` unwrap
+   pub fn add_password(&mut self, argon2: Argon2, credentials: Credentials) -> Result<(), Error> {
+      let wrapped = encrypt_bytes(argon2, &self.key[..], credentials)?;
+      self.push_slot(Slot::Password(wrapped))
+   }
+
+   /// Adds a slot that the identity behind `recipient` unwraps
+   pub fn add_recipient(&mut self, recipient: &Recipient) -> Result<(), Error> {
+      let wrapped = recipient.wrap(&self.key)?;
+      self.push_slot(Slot::Recipient(wrapped))
+   }
+
+   /// Adds a slot that `recovery_key` unwraps
+   pub fn add_recovery(&mut self, recovery_key: &RecoveryKey) -> Result<(), Error> {
+      let wrapped = recovery_key.wrap(&self.key)?;
+      self.push_slot(Slot::Recovery(wrapped))
+   }
+
+   fn push_slot(&mut self, slot: Slot) -> Result<(), Error> {
+      if self.slots.len() >= MAX_SLOTS {
+         return Err(Error::Encrypt(format!(
+            "A file holds at most {} key slots",
+            MAX_SLOTS
+         )));
+      }
+      self.slots.push(slot);
+      Ok(())
+   }
+
+   /// Unwraps a key from the `slots` of a [Header], trying every slot `secret` can open
+   ///
+   /// Password slots carry their own Argon2 parameters, so each one tried costs a full key derivation.
+   pub fn unwrap(slots: &[Slot], secret: &Secret) -> Result<Self, Error> {
+      let (opened, key) = match secret {
+         Secret::Credentials(credentials) => unwrap_password(slots, credentials)?,
+         Secret::Identity(identity) => {
+            let mut recipient_slots = slots
+               .iter()
+               .enumerate()
+               .filter_map(|(i, slot)| match slot {
+                  Slot::Recipient(data) => Some((i, data)),
+                  _ => None,
+               })
+               .peekable();
+
+            if recipient_slots.peek().is_none() {
+               return Err(Error::Decrypt(
+                  "This file was not encrypted to any recipient, use the credentials instead".to_string(),
+               ));
+            }
+
+            recipient_slots
+               .find_map(|(i, data)| identity.unwrap(data).map(|key| (i, key)))
+               .ok_or(Error::Decrypt(
+                  "This file was not encrypted to this identity".to_string(),
+               ))?
+         }
+         Secret::Recovery(recovery_key) => slots
+            .iter()
+            .enumerate()
+            .find_map(|(i, slot)| match slot {
+               Slot::Recovery(data) => recovery_key.unwrap(data).map(|key| (i, key)),
+               _ => None,
+            })
+            .ok_or(Error::Decrypt(
+               "This recovery key does not open the file".to_string(),
+            ))?,
+         Secret::Age(_) => {
+            return Err(Error::Decrypt(
+               "This is an nCrypt file, turn off the age format to decrypt it".to_string(),
+            ));
+         }
+      };
+
+      Ok(Self {
+         key,
+         slots: slots.to_vec(),
+         opened: Some(opened),
+         flags: 0,
+      })
+   }
+
+   /// Removes the slot at `index`, never the one the key was unwrapped from so the file stays accessible
+   pub fn remove_slot(&mut self, index: usize) -> Result<Slot, Error> {
+      if index >= self.slots.len() {
+         return Err(Error::InvalidFormat(format!(
+            "There is no key slot {}",
+            index
+         )));
+      }
+
+      if self.opened == Some(index) {
+         return Err(Error::Decrypt(
+            "This is the slot that was just unlocked, unlock the file with another one to remove it".to_string(),
+         ));
+      }
+
+      if let Some(opened) = self.opened.as_mut()
+         && *opened > index
+      {
+         *opened -= 1;
+      }
+      Ok(self.slots.remove(index))
+   }
+
+   /// Replaces the password slot the key was unwrapped from with one that `credentials` unwrap
+   pub fn replace_password(&mut self, argon2: Argon2, credentials: Credentials) -> Result<(), Error> {
+      let Some(index) = self
+         .opened
+         .filter(|&i| matches!(self.slots[i], Slot::Password(_)))
+      else {
+         return Err(Error::Decrypt(
+            "The file was not unlocked with a password".to_string(),
+         ));
+      };
+
+      let wrapped = encrypt_bytes(argon2, &self.key[..], credentials)?;
+      self.slots[index] = Slot::Password(wrapped);
+      Ok(())
+   }
+
+   /// The slot the key was unwrapped from, if it was unwrapped at all
+   pub fn opened(&self) -> Option<&Slot> {
+      self.opened.map(|i| &self.slots[i])
+   }
+
+   /// Checks that `credentials` unwrap this key again, so whatever it encrypts can be decrypted with them
+   pub fn check(&self, credentials: Credentials) -> Result<(), Error> {
+      let unwrapped = Self::unwrap(&self.slots, &Secret::Credentials(credentials))?;
+      if *unwrapped.key != *self.key {
+         return Err(Error::Verify(
+            "The credentials do not unlock the encrypted files".to_string(),
+         ));
+      }
+      Ok(())
+   }
+
+   pub fn slots(&self) -> &[Slot] {
+      &self.slots
+   }
+
+   fn cipher(&self) -> XChaCha20Poly1305 {
+      XChaCha20Poly1305::new(Key::from_slice(&self.key[..]))
+   }
+}
+
+/// Tries every password slot with `credentials`, reporting the error of the last one if none opens
+///
+/// Returns the index of the slot that opened along with the key
+fn unwrap_password(slots: &[Slot], credentials: &Credentials) -> Result<(usize, Zeroizing<[u8; KEY_SIZE]>), Error> {
+   let mut last_error = Error::Decrypt("This file can only be decrypted with an identity".to_string());
+
+   for (i, slot) in slots.iter().enumerate() {
+      let Slot::Password(wrapped) = slot else {
+         continue;
+      };
+
+      let mut unwrapped = match decrypt_bytes(wrapped.clone(), credentials.clone()) {
+         Ok(unwrapped) => unwrapped,
+         Err(e) => {
+            last_error = e;
+            continue;
+         }
+      };
+
+      if unwrapped.len() != KEY_SIZE {
+         unwrapped.zeroize();
+         last_error = Error::InvalidFormat("Invalid data key".to_string());
+         continue;
+      }
+
+      let mut key = Zeroizing::new([0u8; KEY_SIZE]);
+      key.copy_from_slice(&unwrapped);
+      unwrapped.zeroize();
+      return Ok((i, key));
+   }
+
+   Err(last_error)
+}
+
+/// Remembers unwrapped keys so files encrypted together only pay for Argon2 once when decrypted together
+#[derive(Default)]
+pub struct KeyCache {
+   keys: Vec<DataKey>,
+}
+
+impl KeyCache {
+   pub fn contains(&self, slots: &[Slot]) -> bool {
+      self.keys.iter().any(|key| key.slots == slots)
+   }
+
+   pub fn get_or_unwrap(&mut self, slots: &[Slot], secret: &Secret) -> Result<&DataKey, Error> {
+      let pos = match self.keys.iter().position(|key| key.slots == slots) {
+         Some(pos) => pos,
+         None => {
+            self.keys.push(DataKey::unwrap(slots, secret)?);
+            self.keys.len() - 1
+         }
+      };
+      Ok(&self.keys[pos])
+   }
+}
+
+/// Whether the file at `path` starts with the chunked format [MAGIC]
+pub fn is_chunked_file(path: &Path) -> Result<bool, Error> {
+   let mut file = std::fs::File::open(path).map_err(|e| Error::read(path, e))?;
+   let mut magic = [0u8; 8];
+   let read = read_full(&mut file, &mut magic).map_err(|e| Error::read(path, e))?;
+   Ok(read == magic.len() && &magic == MAGIC)
+}
+
+/// Encrypts everything written to it into the inner writer
+///
+/// [Encryptor::finish] must be called once all data is written, otherwise the last chunk is missing and the
+/// result cannot be decrypted.
+pub struct Encryptor<W: Write> {
+   writer: W,
+   cipher: XChaCha20Poly1305,
+   nonce_prefix: [u8; NONCE_PREFIX_SIZE],
+   aad: Vec<u8>,
+   counter: u32,
+   buffer: Zeroizing<Vec<u8>>,
+}
+
+impl<W: Write> Encryptor<W> {
+   /// Writes the header, every file gets a fresh nonce prefix so one [DataKey] can encrypt many files
+   pub fn new(key: &DataKey, mut writer: W) -> Result<Self, Error> {
+      let cipher = key.cipher();
+      let header = Header::new(key.slots.clone(), key.flags);
+      header.write_to(&mut writer)?;
+
+      Ok(Self {
+         writer,
+         cipher,
+         nonce_prefix: header.nonce_prefix,
+         aad: header.aad(),
+         counter: 0,
+         buffer: Zeroizing::new(Vec::with_capacity(CHUNK_SIZE + TAG_SIZE)),
+      })
+   }
+
+   fn seal_chunk(&mut self, last: bool) -> Result<(), Error> {
+      let nonce = chunk_nonce(&self.nonce_prefix, self.counter, last);
+      self
+         .cipher
+         .encrypt_in_place(&nonce, &self.aad, &mut *self.buffer)
+         .map_err(|e| Error::Encrypt(e.to_string()))?;
+      self.writer.write_all(&self.buffer)?;
+      self.buffer.clear();
+
+      if !last {
+         self.counter = next_counter(self.counter)?;
+      }
+      Ok(())
+   }
+
+   /// Seals the last chunk and returns the inner writer
+   pub fn finish(mut self) -> Result<W, Error> {
+      self.seal_chunk(true)?;
+      self.writer.flush()?;
+      Ok(self.writer)
+   }
+}
+
+impl<W: Write> Write for Encryptor<W> {
+   fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+      let len = buf.len().min(CHUNK_SIZE - self.buffer.len());
+      self.buffer.extend_from_slice(&buf[..len]);
+
+      // A full chunk is never the last one, the last chunk is sealed by finish
+      if self.buffer.len() == CHUNK_SIZE {
+         self.seal_chunk(false).map_err(std::io::Error::other)?;
+      }
+
+      Ok(len)
+   }
+
+   fn flush(&mut self) -> std::io::Result<()> {
+      self.writer.flush()
+   }
+}
+
+/// Decrypts the inner reader as it is read
+///
+/// Only authenticated plaintext is ever returned, but a read can fail midway through the stream if a chunk is
+/// corrupted, so anything already consumed should be discarded in that case.
+pub struct Decryptor<R: Read> {
+   reader: R,
+   cipher: XChaCha20Poly1305,
+   header: Header,
+   aad: Vec<u8>,
+   counter: u32,
+   buffer: Zeroizing<Vec<u8>>,
+   pos: usize,
+   finished: bool,
+}
+
+impl<R: Read> Decryptor<R> {
+   /// Continues after a `header` already read from `reader` with the [DataKey] unwrapped from it
+   pub fn new(key: &DataKey, header: Header, reader: R) -> Result<Self, Error> {
+      if key.slots != header.slots {
+         return Err(Error::Decrypt(
+            "The key does not belong to this file".to_string(),
+         ));
+      }
+
+      let cipher = key.cipher();
+      let sealed_chunk_size = header.chunk_size as usize + TAG_SIZE;
+
+      Ok(Self {
+         reader,
+         cipher,
+         aad: header.aad(),
+         header,
+         counter: 0,
+         buffer: Zeroizing::new(Vec::with_capacity(sealed_chunk_size)),
+         pos: 0,
+         finished: false,
+      })
+   }
+
+   pub fn header(&self) -> &Header {
+      &self.header
+   }
+
+   fn open_chunk(&mut self) -> Result<(), Error> {
+      let sealed_chunk_size = self.header.chunk_size as usize + TAG_SIZE;
+
+      self.buffer.resize(sealed_chunk_size, 0);
+      let read = read_full(&mut self.reader, &mut self.buffer)?;
+      self.buffer.truncate(read);
+      let last = read < sealed_chunk_size;
+
+      let nonce = chunk_nonce(&self.header.nonce_prefix, self.counter, last);
+      self
+         .cipher
+         .decrypt_in_place(&nonce, &self.aad, &mut *self.buffer)
+         .map_err(|_| Error::Decrypt("Chunk failed authentication, the file is truncated or corrupted".to_string()))?;
+      self.pos = 0;
+
+      if last {
+         self.finished = true;
+      } else {
+         self.counter = next_counter(self.counter)?;
+      }
+      Ok(())
+   }
+}
+
+impl<R: Read> Read for Decryptor<R> {
+   fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+      while self.pos == self.buffer.len() {
+         if self.finished {
+            return Ok(0);
+         }
+         self.open_chunk().map_err(std::io::Error::other)?;
+      }
+
+      let len = buf.len().min(self.buffer.len() - self.pos);
+      buf[..len].copy_from_slice(&self.buffer[self.pos..self.pos + len]);
+      self.pos += len;
+      Ok(len)
+   }
+}
+
+fn chunk_nonce(prefix: &[u8; NONCE_PREFIX_SIZE], counter: u32, last: bool) -> XNonce {
+   let mut nonce = XNonce::default();
+   nonce[..NONCE_PREFIX_SIZE].copy_from_slice(prefix);
+   nonce[NONCE_PREFIX_SIZE..NONCE_PREFIX_SIZE + 4].copy_from_slice(&counter.to_be_bytes());
+   nonce[NONCE_PREFIX_SIZE + 4] = last as u8;
+   nonce
+}
+
+fn next_counter(counter: u32) -> Result<u32, Error> {
+   counter
+      .checked_add(1)
+      .ok_or(Error::InvalidFormat("Too many chunks".to_string()))
+}
+
+fn read_slot_data<R: Read>(reader: &mut R) -> Result<Vec<u8>, Error> {
+   let len = read_u32(reader)? as usize;
+   if len > MAX_WRAPPED_KEY_SIZE {
+      return Err(Error::InvalidFormat(format!("Invalid key size {}", len)));
+   }
+
+   let mut data = vec![0u8; len];
+   reader.read_exact(&mut data)?;
+   Ok(data)
+}
+
+fn read_u32<R: Read>(reader: &mut R) -> Result<u32, Error> {
+   let mut bytes = [0u8; 4];
+   reader.read_exact(&mut bytes)?;
+   Ok(u32::from_le_bytes(bytes))
+}
+
+/// Like [Read::read_exact] but stops at EOF, returning how many bytes were read
+fn read_full<R: Read>(reader: &mut R, buf: &mut [u8]) -> std::io::Result<usize> {
+   let mut read = 0;
+   while read < buf.len() {
+      match reader.read(&mut buf[read..]) {
+         Ok(0) => break,
+         Ok(n) => read += n,
+         Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+         Err(e) => return Err(e),
+      }
+   }
+   Ok(read)
+}
@@ -0,0 +1,55 @@
+//! Checking that an encrypted file decrypts back to the original
+//!
+//! The encrypted file is read back from disk and decrypted in full, so every chunk is authenticated, and
+//! SHA3-256 digests of the decrypted data are compared against the original.
+
+use super::{
+   Error, PayloadKind, archive,
+   progress::{Phase, Progress, ProgressReader},
+   read_payload_start,
+   stream::{DataKey, Decryptor, Header},
+};
+use sha3::{Digest, Sha3_256};
+use std::fs::File;
+use std::io::{BufReader, Read};
+use std::path::Path;
+
+/// Decrypts the file at `encrypted_path` with `key` and compares it against the file or directory at
+/// `original_path`
+pub fn verify(key: &DataKey, original_path: &Path, encrypted_path: &Path, progress: &Progress) -> Result<(), Error> {
+   let file = File::open(encrypted_path).map_err(|e| Error::read(encrypted_path, e))?;
+   let total = file.metadata().map(|m| m.len()).unwrap_or(0);
+   let mut reader = BufReader::new(file);
+   let header = Header::read_from(&mut reader)?;
+
+   progress.start(Phase::Verifying, total.saturating_sub(header.size() as u64));
+   let reader = ProgressReader::new(reader, progress);
+   let mut reader = Decryptor::new(key, header, reader)?;
+   let (kind, _) = read_payload_start(&mut reader)?;
+
+   match kind {
+      PayloadKind::File => {
+         let original = File::open(original_path).map_err(|e| Error::read(original_path, e))?;
+         let expected = digest(BufReader::new(original)).map_err(|e| match e {
+            Error::Io(e) => Error::read(original_path, e),
+            e => e,
+         })?;
+
+         if digest(reader)? != expected {
+            return Err(Error::Verify(format!(
+               "The encrypted copy of {} does not match the original",
+               original_path.display()
+            )));
+         }
+         Ok(())
+      }
+      PayloadKind::Directory => archive::verify(reader, original_path),
+   }
+}
+
+/// The SHA3-256 digest of everything `reader` returns
+pub fn digest<R: Read>(mut reader: R) -> Result<[u8; 32], Error> {
+   let mut hasher = Sha3_256::new();
+   std::io::copy(&mut reader, &mut hasher)?;
+   Ok(hasher.finalize().into())
+}
@@ -1,36 +1,86 @@
-use crate::gui::{GUI, SHARED_GUI};
+use crate::gui::{GUI, SHARED_GUI, settings::Settings};
 use eframe::{
    CreationContext,
    egui::{CentralPanel, Context, Frame, Panel, Rgba, Ui, Visuals},
 };
+use std::sync::mpsc::{self, Sender};
 
 /// The main application struct
 pub struct NCryptApp {
    pub style_has_been_set: bool,
+   /// The settings as last saved
+   pub saved_settings: Settings,
+   /// Hands changed settings to the thread that writes them to disk
+   settings_writer: Sender<Settings>,
 }
 
 impl NCryptApp {
    pub fn new(cc: &CreationContext) -> Self {
-      let theme = SHARED_GUI.read(|gui| gui.theme.clone());
+      let settings = Settings::load();
+      let theme = SHARED_GUI.write(|gui| {
+         gui.apply_settings(&settings);
+         gui.theme.clone()
+      });
 
       cc.egui_ctx.set_global_style(theme.style.clone());
 
-      let app = Self {
+      Self {
          style_has_been_set: false,
-      };
+         saved_settings: settings,
+         settings_writer: spawn_settings_writer(),
+      }
+   }
+
+   /// Saves the settings once they changed, waiting for the mouse to be released so dragging a slider
+   /// doesn't write the file on every frame
+   fn save_settings(&mut self, ctx: &Context, gui: &mut GUI) {
+      if ctx.input(|i| i.pointer.any_down()) {
+         return;
+      }
+
+      let settings = gui.settings();
+      if settings == self.saved_settings {
+         return;
+      }
 
-      app
+      // Writing the file syncs it to disk, which is too slow for the UI thread
+      let _ = self.settings_writer.send(settings.clone());
+      self.saved_settings = settings;
    }
 
    fn on_shutdown(&mut self, ctx: &Context, gui: &mut GUI) {
       if ctx.input(|i| i.viewport().close_requested()) {
          gui.file_encryption.credentials_form.erase();
+         gui.file_encryption.slots.credentials_form.erase();
+         gui.file_encryption.recovery.input.erase();
          gui.text_hashing.input_text.erase();
          gui.text_hashing.output_hash.erase();
       }
    }
 }
 
+/// Starts the thread that writes the settings sent to it, only the latest of those sent meanwhile is written
+fn spawn_settings_writer() -> Sender<Settings> {
+   let (sender, receiver) = mpsc::channel::<Settings>();
+
+   std::thread::spawn(move || {
+      while let Ok(mut settings) = receiver.recv() {
+         while let Ok(newer) = receiver.try_recv() {
+            settings = newer;
+         }
+
+         if let Err(e) = settings.save() {
+            SHARED_GUI.write(|gui| {
+               gui.msg_window
+                  .open_with_msg(format!("Failed to save the settings: {}", e));
+            });
+         }
+      }
+   });
+
+   sender
+}
+
 impl eframe::App for NCryptApp {
    fn clear_color(&self, _visuals: &Visuals) -> [f32; 4] {
       Rgba::TRANSPARENT.to_array()
@@ -39,6 +89,7 @@ impl eframe::App for NCryptApp {
    fn ui(&mut self, ui: &mut Ui, _frame: &mut eframe::Frame) {
       SHARED_GUI.write(|gui| {
          self.on_shutdown(ui.ctx(), gui);
+         self.save_settings(ui.ctx(), gui);
 
          // This is needed for Windows
          if !self.style_has_been_set {
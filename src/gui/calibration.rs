@@ -0,0 +1,170 @@
+use super::SHARED_GUI;
+use crate::core::{
+   Preset, Progress,
+   calibrate::{Calibration, MIN_M_COST, calibrate, reference},
+   memory,
+};
+use eframe::egui::{RichText, Slider, Ui, vec2};
+use ncrypt_me::Argon2;
+use std::time::Duration;
+use zeus_theme::Theme;
+use zeus_widgets::{Button, ComboBox, Label};
+
+/// The derivation times to choose from, in seconds
+const TARGETS: [u64; 3] = [1, 3, 10];
+
+/// Benchmarks Argon2 to pick parameters for a target time
+pub struct CalibrationUi {
+   pub target_secs: u64,
+   /// The most memory the calibration may use, in KiB
+   pub memory_budget: u32,
+   pub running: bool,
+   /// What the last calibration measured
+   pub result: Option<String>,
+   /// The measurement time estimates are based on
+   pub reference: Option<Calibration>,
+   /// Whether the quick reference measurement is running
+   pub measuring: bool,
+}
+
+impl Default for CalibrationUi {
+   fn default() -> Self {
+      Self::new()
+   }
+}
+
+impl CalibrationUi {
+   pub fn new() -> Self {
+      Self {
+         target_secs: 3,
+         memory_budget: 1_048_576,
+         running: false,
+         result: None,
+         reference: None,
+         measuring: false,
+      }
+   }
+
+   /// Roughly how long `argon2` takes here, once a calibration or [Self::measure] ran
+   pub fn estimate(&self, argon2: &Argon2) -> Option<Duration> {
+      self
+         .reference
+         .as_ref()
+         .map(|reference| reference.estimate(argon2))
+   }
+
+   /// Runs a quick measurement in the background for the time estimates of the presets
+   fn measure(&mut self) {
+      self.measuring = true;
+
+      std::thread::spawn(|| {
+         let res = reference();
+         SHARED_GUI.write(|gui| {
+            gui.calibration.measuring = false;
+            match res {
+               Ok(calibration) => {
+                  gui.calibration.reference.get_or_insert(calibration);
+               }
+               Err(e) => gui.msg_window.open_with_msg(e.to_string()),
+            }
+         });
+      });
+   }
+
+   /// `max_m_cost` is the upper end of the memory budget, like that of the memory cost slider
+   pub fn show(&mut self, theme: &Theme, max_m_cost: u32, ui: &mut Ui) {
+      ui.label(RichText::new("Calibrate").size(theme.text_sizes.normal))
+         .on_hover_text("Benchmark Argon2 on this computer and pick parameters that take the target time");
+
+      let label_text = RichText::new(format!("{} s", self.target_secs)).size(theme.text_sizes.normal);
+      let label = Label::new(label_text, None);
+
+      ComboBox::new("calibration_target", label)
+         .visuals(theme.combo_box_visuals())
+         .width(100.0)
+         .show_ui(ui, |ui| {
+            ui.spacing_mut().button_padding = vec2(5.0, 5.0);
+
+            for target in TARGETS {
+               ui.selectable_value(
+                  &mut self.target_secs,
+                  target,
+                  RichText::new(format!("{} s", target)).size(theme.text_sizes.normal),
+               );
+            }
+         });
+
+      ui.label(RichText::new("Memory budget:").size(theme.text_sizes.normal));
+      self.memory_budget = self.memory_budget.clamp(MIN_M_COST, max_m_cost);
+      ui.add(
+         Slider::new(&mut self.memory_budget, MIN_M_COST..=max_m_cost)
+            .custom_formatter(|v, _ctx| memory::format_kib(v as u64)),
+      );
+
+      let text = RichText::new("Calibrate").size(theme.text_sizes.normal);
+      let button = Button::new(text).visuals(theme.button_visuals());
+      if ui
+         .add_enabled(!self.running && !self.measuring, button)
+         .clicked()
+      {
+         self.start();
+      }
+
+      if self.reference.is_none() {
+         let text = RichText::new("Estimate Preset Times").size(theme.text_sizes.normal);
+         let button = Button::new(text).visuals(theme.button_visuals());
+         let clicked = ui
+            .add_enabled(!self.running && !self.measuring, button)
+            .on_hover_text("Runs Argon2 once with small parameters to estimate how long each preset takes")
+            .clicked();
+         if clicked {
+            self.measure();
+         }
+      }
+
+      if self.measuring {
+         ui.label(RichText::new("Measuring Argon2...").size(theme.text_sizes.small));
+      }
+
+      if let Some(result) = &self.result {
+         ui.label(RichText::new(result).size(theme.text_sizes.small));
+      }
+   }
+
+   fn start(&mut self) {
+      self.running = true;
+      self.result = None;
+
+      let target = Duration::from_secs(self.target_secs);
+      let memory_budget = self.memory_budget;
+      let progress = Progress::new();
+
+      std::thread::spawn(move || {
+         SHARED_GUI.write(|gui| {
+            gui.msg_window
+               .open_with_progress("Calibrating...", progress.clone());
+         });
+
+         let res = calibrate(target, memory_budget, &progress);
+
+         SHARED_GUI.write(|gui| {
+            gui.calibration.running = false;
+            match res {
+               Ok(calibration) => {
+                  gui.calibration.result = Some(format!(
+                     "Measured {:.1} s for {}, {} iterations",
+                     calibration.measured.as_secs_f64(),
+                     memory::format_kib(calibration.argon2.m_cost as u64),
+                     calibration.argon2.t_cost
+                  ));
+                  gui.argon2 = calibration.argon2.clone();
+                  gui.preset = Preset::from_argon2(&gui.argon2);
+                  gui.calibration.reference = Some(calibration);
+                  gui.msg_window.open = false;
+               }
+               Err(e) => gui.msg_window.open_with_msg(e.to_string()),
+            }
+         });
+      });
+   }
+}
@@ -1,18 +1,96 @@
+use super::recipients::RecipientsUi;
+use super::recovery::{RecoveryUi, scan_qr};
+use super::slots::{SlotChange, SlotsUi};
 use super::*;
-use eframe::egui::{Align, DroppedFileHandle, Label, Layout, RichText, Ui};
-use ncrypt_me::{Argon2, Credentials, decrypt::decrypt_data_unsecured, encrypt::encrypt_data_ref};
+use crate::core::{
+   Error, Options, Overwrite, Progress, Recipient, RecoveryKey, Secret,
+   age_format::{self, AgeSecret, AgeTarget},
+   batch::{JobStatus, decrypt_batch, encrypt_batch_age, encrypt_batch_with_key, rekey_batch},
+   decrypted_path, derive_key, encrypted_path, inspect,
+   keyfile::{self, Keyfile},
+   rekey, shamir, shred, slots,
+   stream::DataKey,
+};
+use eframe::egui::{Align, Label, Layout, RichText, ScrollArea, Ui};
+use ncrypt_me::{Argon2, secure_types::SecureString};
+use std::path::{Path, PathBuf};
+use zeroize::Zeroizing;
 use zeus_theme::Theme;
 use zeus_ui_components::CredentialsForm;
-use zeus_widgets::{Button, Zeroize};
+use zeus_widgets::{Button, Modal};
 
-const FILE_EXTENSION: &str = ".ncrypt";
+/// A file or folder waiting in the queue
+pub struct QueuedFile {
+   pub path: PathBuf,
+   pub status: JobStatus,
+}
+
+/// What the user asked to do with the queue
+#[derive(Clone)]
+pub enum Action {
+   Encrypt(Argon2),
+   Decrypt,
+}
+
+/// Who the queued files get encrypted for
+enum Target {
+   /// The username, password and keyfiles, mixed together in the worker thread
+   Credentials(SecureString, SecureString, Vec<Keyfile>),
+   Recipients(Vec<Recipient>),
+   Age(AgeTarget),
+}
+
+/// What the queued files get decrypted with
+enum Unlock {
+   /// The username, password and keyfiles, mixed together in the worker thread
+   Credentials(SecureString, SecureString, Vec<Keyfile>),
+   Secret(Secret),
+}
+
+/// An action on hold until the user decides what happens to the outputs that already exist
+pub struct OverwritePrompt {
+   pub action: Action,
+   pub options: Options,
+   /// The queued files to run the action on
+   pub inputs: Vec<PathBuf>,
+   pub existing: Vec<PathBuf>,
+}
 
 /// File Encryption/Decryption Ui
 pub struct FileEncryptionUi {
    pub open: bool,
    pub credentials_form: CredentialsForm,
-   pub file_path: String,
-   pub dropped_file: Option<DroppedFileHandle>,
+   pub queue: Vec<QueuedFile>,
+   /// Whether a batch is being processed, the queue can't be edited meanwhile
+   pub running: bool,
+   pub overwrite_prompt: Option<OverwritePrompt>,
+   /// Ask where to save the outputs instead of writing them next to the inputs
+   pub save_as: bool,
+   /// Give encrypted files random names
+   pub neutral_name: bool,
+   /// Check that the encrypted files decrypt back to the originals
+   pub verify: bool,
+   /// Remove the originals after encryption
+   pub shred: bool,
+   /// Run Argon2 even if it needs more memory than is available
+   pub ignore_memory_limit: bool,
+   /// Where the file dialogs open
+   pub last_dir: Option<PathBuf>,
+   /// Keyfiles mixed into the credentials
+   pub keyfiles: Vec<Keyfile>,
+   /// Why reading a QR code picture for the credentials failed
+   pub scan_error: Option<String>,
+   pub recipients: RecipientsUi,
+   /// Write and read files in the age format instead
+   pub age: bool,
+   pub slots: SlotsUi,
+   pub recovery: RecoveryUi,
+}
+
+impl Default for FileEncryptionUi {
+   fn default() -> Self {
+      Self::new()
+   }
 }
 
 impl FileEncryptionUi {
@@ -23,57 +101,129 @@ impl FileEncryptionUi {
       Self {
          open: true,
          credentials_form: form,
-         file_path: String::new(),
-         dropped_file: None,
+         queue: Vec::new(),
+         running: false,
+         overwrite_prompt: None,
+         save_as: false,
+         neutral_name: false,
+         verify: false,
+         shred: false,
+         ignore_memory_limit: false,
+         last_dir: None,
+         keyfiles: Vec::new(),
+         scan_error: None,
+         recipients: RecipientsUi::new(),
+         age: false,
+         slots: SlotsUi::new(),
+         recovery: RecoveryUi::new(),
       }
    }
 
+   /// Adds a path to the queue unless it is already in it
+   pub fn add_to_queue(&mut self, path: PathBuf) {
+      if self.running {
+         return;
+      }
+
+      if let Some(file) = self.queue.iter_mut().find(|file| file.path == path) {
+         file.status = JobStatus::Queued;
+         return;
+      }
+
+      self.queue.push(QueuedFile {
+         path,
+         status: JobStatus::Queued,
+      });
+   }
+
    pub fn show(&mut self, theme: &Theme, argon2: Argon2, ui: &mut Ui) {
       if !self.open {
          return;
       }
 
+      self.show_overwrite_prompt(theme, ui);
+
+      if let Some(change) = self.slots.show(theme, ui) {
+         self.change_slots(argon2.clone(), change);
+      }
+
+      self.recovery.show_shares(theme, ui);
+      self.recovery.show_key(theme, ui);
+
       ui.vertical_centered(|ui| {
          ui.set_width(ui.available_width());
          ui.set_height(ui.available_height());
          ui.spacing_mut().item_spacing.y = 15.0;
          ui.spacing_mut().button_padding = vec2(10.0, 8.0);
 
-         let text = RichText::new("Drag and drop or select a file").size(theme.text_sizes.normal);
+         let text = RichText::new("Drag and drop or select files and folders").size(theme.text_sizes.normal);
          let label = Label::new(text).wrap();
          ui.scope(|ui| {
             ui.set_max_width(ui.available_width() * 0.5);
             ui.add(label);
          });
 
-         // Collect dropped file
-         ui.ctx().input(|i| {
-            if let Some(first_file) = i.raw.dropped_files.first() {
-               self.dropped_file = Some(first_file.clone());
-            }
+         // Collect dropped files
+         let dropped: Vec<PathBuf> = ui.ctx().input(|i| {
+            i.raw
+               .dropped_files
+               .iter()
+               .map(|file| file.path().to_path_buf())
+               .collect()
          });
 
-         let button =
-            Button::new(RichText::new("Choose a File").size(theme.text_sizes.normal)).visuals(theme.button_visuals());
-         if ui.add(button).clicked() {
-            if let Some(path) = rfd::FileDialog::new().pick_file() {
-               self.file_path = path.to_str().unwrap().to_string();
-            }
+         for path in dropped {
+            self.add_to_queue(path);
          }
 
-         if let Some(dropped_file) = self.dropped_file.as_ref() {
-            self.file_path = dropped_file.path().to_string_lossy().to_string();
-            self.dropped_file = None;
-         }
+         let size = vec2(ui.available_width() * 0.5, 30.0);
+         ui.allocate_ui_with_layout(size, Layout::left_to_right(Align::Center), |ui| {
+            ui.spacing_mut().item_spacing.x = 20.0;
 
-         if !self.file_path.is_empty() {
-            let mut path = self.file_path.clone();
-            if path.len() > 50 {
-               path = path.chars().take(50).collect::<String>() + "...";
+            let btn_size = vec2(130.0, 30.0);
+            let text = RichText::new("Choose Files").size(theme.text_sizes.normal);
+            let button = Button::new(text)
+               .visuals(theme.button_visuals())
+               .min_size(btn_size);
+
+            if ui.add(button).clicked()
+               && let Some(paths) = self.file_dialog().pick_files()
+            {
+               self.remember_dir(paths.first());
+               for path in paths {
+                  self.add_to_queue(path);
+               }
             }
-            let file_text = RichText::new(path).size(theme.text_sizes.small).strong();
-            ui.label(file_text);
-         }
+
+            let text = RichText::new("Choose a Folder").size(theme.text_sizes.normal);
+            let button = Button::new(text)
+               .visuals(theme.button_visuals())
+               .min_size(btn_size);
+
+            if ui.add(button).clicked()
+               && let Some(path) = self.file_dialog().pick_folder()
+            {
+               self.remember_dir(Some(&path));
+               self.add_to_queue(path);
+            }
+
+            let text = RichText::new("Find Weak Files").size(theme.text_sizes.normal);
+            let button = Button::new(text)
+               .visuals(theme.button_visuals())
+               .min_size(btn_size);
+
+            let clicked = ui
+               .add_enabled(!self.running, button)
+               .on_hover_text("Queue the encrypted files in a folder whose Argon2 parameters are below the current ones")
+               .clicked();
+
+            if clicked && let Some(dir) = self.file_dialog().pick_folder() {
+               self.remember_dir(Some(&dir));
+               self.scan(dir, argon2.clone());
+            }
+         });
+
+         self.show_queue(theme, ui);
 
          ui.add_space(10.0);
 
@@ -89,9 +239,30 @@ impl FileEncryptionUi {
             self.credentials_form.show(theme, ui);
          });
 
+         self.show_keyfiles(theme, ui);
+
+         let credentials_form = &self.credentials_form;
+         let keyfiles = &self.keyfiles;
+         self.recipients.show(
+            theme,
+            argon2.clone(),
+            || {
+               (
+                  credentials_form.username(),
+                  credentials_form.password(),
+                  keyfiles.clone(),
+               )
+            },
+            ui,
+         );
+
+         self.recovery.show(theme, ui);
+
+         // Five buttons don't fit next to each other in half the panel, so the row wraps
          let size = vec2(ui.available_width() * 0.5, 30.0);
-         ui.allocate_ui_with_layout(size, Layout::left_to_right(Align::Center), |ui| {
-            ui.spacing_mut().item_spacing.x = 20.0;
+         let layout = Layout::left_to_right(Align::Center).with_main_wrap(true);
+         ui.allocate_ui_with_layout(size, layout, |ui| {
+            ui.spacing_mut().item_spacing = vec2(20.0, 10.0);
 
             let btn_size = vec2(130.0, 30.0);
             let text = RichText::new("Encrypt").size(theme.text_sizes.normal);
@@ -99,7 +270,7 @@ impl FileEncryptionUi {
             let button = Button::new(text).visuals(visuals).min_size(btn_size);
 
             if ui.add(button).clicked() {
-               self.encrypt(argon2);
+               self.request(Action::Encrypt(argon2.clone()));
             }
 
             let text = RichText::new("Decrypt").size(theme.text_sizes.normal);
@@ -107,119 +278,844 @@ impl FileEncryptionUi {
             let button = Button::new(text).visuals(visuals).min_size(btn_size);
 
             if ui.add(button).clicked() {
-               self.decrypt();
+               self.request(Action::Decrypt);
+            }
+
+            let text = RichText::new("Inspect").size(theme.text_sizes.normal);
+            let visuals = theme.button_visuals();
+            let button = Button::new(text).visuals(visuals).min_size(btn_size);
+
+            if ui
+               .add(button)
+               .on_hover_text("Show how the queued files were encrypted, no credentials needed")
+               .clicked()
+            {
+               self.inspect();
+            }
+
+            let text = RichText::new("Key Slots").size(theme.text_sizes.normal);
+            let visuals = theme.button_visuals();
+            let button = Button::new(text).visuals(visuals).min_size(btn_size);
+            let enabled = !self.running && self.queue.len() == 1 && !self.age;
+
+            if ui
+               .add_enabled(enabled, button)
+               .on_hover_text("Add, remove or change the passwords and public keys that open the queued file")
+               .clicked()
+            {
+               self.open_slots();
+            }
+
+            let text = RichText::new("Re-key").size(theme.text_sizes.normal);
+            let visuals = theme.button_visuals();
+            let button = Button::new(text).visuals(visuals).min_size(btn_size);
+
+            if ui
+               .add_enabled(!self.age, button)
+               .on_hover_text(
+                  "Re-encrypt the queued files in place with the current Argon2 parameters, they keep opening with the same credentials",
+               )
+               .clicked()
+            {
+               self.rekey(argon2.clone());
             }
          });
+
+         let size = vec2(ui.available_width() * 0.5, 20.0);
+         let layout = Layout::left_to_right(Align::Center).with_main_wrap(true);
+         ui.allocate_ui_with_layout(size, layout, |ui| {
+            ui.spacing_mut().item_spacing = vec2(20.0, 5.0);
+
+            let text = RichText::new("Save as...").size(theme.text_sizes.normal);
+            ui.checkbox(&mut self.save_as, text)
+               .on_hover_text("Choose where to save the results");
+
+            let text = RichText::new("Hide file names").size(theme.text_sizes.normal);
+            ui.checkbox(&mut self.neutral_name, text)
+               .on_hover_text("Give encrypted files a random name, the original name is restored on decryption");
+
+            let text = RichText::new("Verify").size(theme.text_sizes.normal);
+            ui.checkbox(&mut self.verify, text)
+               .on_hover_text("Decrypt the encrypted files again and compare them against the originals");
+
+            let text = RichText::new("Shred originals").size(theme.text_sizes.normal);
+            ui.checkbox(&mut self.shred, text).on_hover_text(format!(
+               "Verify the encrypted files, then overwrite and delete the originals.\n{}",
+               shred::CAVEAT
+            ));
+
+            let text = RichText::new("age format").size(theme.text_sizes.normal);
+            ui.checkbox(&mut self.age, text).on_hover_text(
+               "Write and read files in the age format, so they can be exchanged with age and rage users. \
+               Uses the password as the passphrase, or the age1 public keys and age identity with public keys",
+            );
+
+            let text = RichText::new("Ignore memory limit").size(theme.text_sizes.normal);
+            ui.checkbox(&mut self.ignore_memory_limit, text)
+               .on_hover_text(
+                  "Start even if Argon2 needs more memory than is available, this may freeze your computer",
+               );
+         });
       });
    }
 
-   fn encrypt(&mut self, argon2: Argon2) {
-      let file_path = self.file_path.clone();
-      let username = self.credentials_form.username();
-      let password = self.credentials_form.password();
-      let credentials = Credentials::new(username, password.clone(), password);
+   fn show_keyfiles(&mut self, theme: &Theme, ui: &mut Ui) {
+      let size = vec2(ui.available_width() * 0.5, 30.0);
+      ui.allocate_ui_with_layout(size, Layout::left_to_right(Align::Center), |ui| {
+         ui.spacing_mut().item_spacing.x = 20.0;
 
-      std::thread::spawn(move || {
-         let mut data = match std::fs::read(&file_path) {
-            Ok(data) => data,
-            Err(e) => {
-               SHARED_GUI.write(|gui| {
-                  gui.msg_window
-                     .open_with_msg(format!("Error reading file: {}", e));
-               });
-               return;
+         let text = RichText::new("Add Keyfiles").size(theme.text_sizes.normal);
+         let button = Button::new(text).visuals(theme.button_visuals());
+         let clicked = ui
+            .add_enabled(!self.running, button)
+            .on_hover_text("Files that must be present alongside the password to decrypt")
+            .clicked();
+
+         if clicked && let Some(paths) = self.file_dialog().pick_files() {
+            for path in paths {
+               self.add_keyfile(Keyfile::Path(path));
             }
-         };
+         }
 
-         SHARED_GUI.write(|gui| {
-            gui.msg_window.open_with_loading("Encrypting...");
-         });
+         let text = RichText::new("Keyfile QR").size(theme.text_sizes.normal);
+         let button = Button::new(text).visuals(theme.button_visuals());
+         let clicked = ui
+            .add_enabled(!self.running, button)
+            .on_hover_text("Use the text of a QR code in a picture as the contents of a keyfile")
+            .clicked();
 
-         let encrypted_data = match encrypt_data_ref(argon2, &data, credentials) {
-            Ok(data) => data,
-            Err(e) => {
-               data.zeroize();
-               SHARED_GUI.write(|gui| {
-                  gui.msg_window
-                     .open_with_msg(format!("Error encrypting file: {}", e));
-               });
-               return;
+         if clicked && let Some(texts) = self.scan_picture() {
+            for text in texts {
+               self.add_keyfile(Keyfile::Contents(Zeroizing::new(text.as_bytes().to_vec())));
             }
-         };
+         }
+
+         let text = RichText::new("Password QR").size(theme.text_sizes.normal);
+         let button = Button::new(text).visuals(theme.button_visuals());
+         let clicked = ui
+            .add_enabled(!self.running, button)
+            .on_hover_text("Fill in the password from the QR code in a picture")
+            .clicked();
+
+         if clicked
+            && let Some(text) = self
+               .scan_picture()
+               .and_then(|texts| texts.into_iter().next())
+         {
+            self
+               .credentials_form
+               .set_password_text(SecureString::from(text.as_str()));
+            self
+               .credentials_form
+               .set_confirm_password_text(SecureString::from(text.as_str()));
+         }
+
+         if let Some(e) = &self.scan_error {
+            ui.label(RichText::new(e).size(theme.text_sizes.small));
+         }
 
-         data.zeroize();
+         if self.keyfiles.is_empty() {
+            return;
+         }
+
+         let names: Vec<String> = self
+            .keyfiles
+            .iter()
+            .map(|keyfile| match keyfile {
+               Keyfile::Path(path) => path.to_string_lossy().to_string(),
+               Keyfile::Contents(_) => "Scanned from a QR code".to_string(),
+            })
+            .collect();
+         ui.label(RichText::new(format!("{} keyfiles", self.keyfiles.len())).size(theme.text_sizes.normal))
+            .on_hover_text(names.join("\n"));
+
+         let text = RichText::new("Clear").size(theme.text_sizes.small);
+         let button = Button::new(text).visuals(theme.button_visuals());
+         if ui.add_enabled(!self.running, button).clicked() {
+            self.keyfiles.clear();
+         }
+      });
+   }
+
+   fn add_keyfile(&mut self, keyfile: Keyfile) {
+      if !self.keyfiles.contains(&keyfile) {
+         self.keyfiles.push(keyfile);
+      }
+   }
+
+   /// The texts of the QR codes in a picture, `None` if no picture was picked or reading it failed
+   fn scan_picture(&mut self) -> Option<Vec<Zeroizing<String>>> {
+      match scan_qr() {
+         Ok(texts) => {
+            self.scan_error = None;
+            (!texts.is_empty()).then_some(texts)
+         }
+         Err(e) => {
+            self.scan_error = Some(e.to_string());
+            None
+         }
+      }
+   }
+
+   fn show_queue(&mut self, theme: &Theme, ui: &mut Ui) {
+      if self.queue.is_empty() {
+         return;
+      }
 
-         let new_file_path = format!("{}{}", file_path, FILE_EXTENSION);
+      let mut remove = None;
 
-         match std::fs::write(&new_file_path, encrypted_data) {
-            Ok(_) => {
-               SHARED_GUI.write(|gui| {
-                  gui.msg_window
-                     .open_with_msg(format!("File encrypted successfully to {}", new_file_path));
+      ui.scope(|ui| {
+         ui.set_max_width(ui.available_width() * 0.6);
+         ui.spacing_mut().item_spacing.y = 5.0;
+         ui.spacing_mut().button_padding = vec2(4.0, 2.0);
+
+         ScrollArea::vertical().max_height(90.0).show(ui, |ui| {
+            for (i, file) in self.queue.iter().enumerate() {
+               ui.horizontal(|ui| {
+                  let mut name = file.path.to_string_lossy().to_string();
+                  if name.len() > 40 {
+                     name = name.chars().take(40).collect::<String>() + "...";
+                  }
+                  ui.label(RichText::new(name).size(theme.text_sizes.small).strong());
+
+                  ui.with_layout(Layout::right_to_left(Align::Center), |ui| {
+                     let text = RichText::new("x").size(theme.text_sizes.small);
+                     let button = Button::new(text).visuals(theme.button_visuals());
+                     if ui.add_enabled(!self.running, button).clicked() {
+                        remove = Some(i);
+                     }
+
+                     let status = ui.label(RichText::new(file.status.to_string()).size(theme.text_sizes.small));
+                     match &file.status {
+                        JobStatus::Done(output) => {
+                           status.on_hover_text(output.to_string_lossy());
+                        }
+                        JobStatus::Failed(e) => {
+                           status.on_hover_text(e);
+                        }
+                        _ => {}
+                     }
+                  });
                });
             }
-            Err(e) => {
-               SHARED_GUI.write(|gui| {
-                  gui.msg_window
-                     .open_with_msg(format!("Error writing file: {}", e));
+         });
+
+         let text = RichText::new("Clear").size(theme.text_sizes.small);
+         let button = Button::new(text).visuals(theme.button_visuals());
+         if ui.add_enabled(!self.running, button).clicked() {
+            self.queue.clear();
+         }
+      });
+
+      if let Some(i) = remove {
+         self.queue.remove(i);
+      }
+   }
+
+   /// Runs `action` right away, or asks first if some outputs already exist
+   fn request(&mut self, action: Action) {
+      if self.running || self.queue.is_empty() {
+         return;
+      }
+
+      let mut options = Options {
+         neutral_name: self.neutral_name,
+         verify: self.verify,
+         shred: self.shred,
+         ignore_memory_limit: self.ignore_memory_limit,
+         keyfile: !self.keyfiles.is_empty() && !self.recipients.enabled && !self.age,
+         ..Default::default()
+      };
+      if self.save_as && !self.choose_destination(&action, &mut options) {
+         return;
+      }
+
+      // Decrypted files may restore a stored name, those that already exist are caught while decrypting
+      let existing: Vec<PathBuf> = self
+         .queue
+         .iter()
+         .map(|file| match action {
+            Action::Encrypt(_) if self.age => options.output_for(age_format::encrypted_path(&file.path)),
+            Action::Encrypt(_) => options.encrypted_output(&file.path),
+            Action::Decrypt => options.output_for(self.decrypted_path(&file.path)),
+         })
+         .filter(|path| path.exists())
+         .collect();
+
+      let inputs = self.queued_paths();
+      if existing.is_empty() {
+         self.run(action, options, inputs);
+      } else {
+         self.overwrite_prompt = Some(OverwritePrompt {
+            action,
+            options,
+            inputs,
+            existing,
+         });
+      }
+   }
+
+   /// Asks for a file name when encrypting a single file or a folder otherwise
+   ///
+   /// Decrypted files keep the name stored inside them, which is only known once decrypting, so decrypting always
+   /// asks for a folder.
+   ///
+   /// Returns false if the user closed the dialog
+   fn choose_destination(&self, action: &Action, options: &mut Options) -> bool {
+      if let (Action::Encrypt(_), [file]) = (action, self.queue.as_slice()) {
+         let default_path = if self.age {
+            age_format::encrypted_path(&file.path)
+         } else {
+            encrypted_path(&file.path)
+         };
+
+         let mut dialog = rfd::FileDialog::new();
+         if let Some(name) = default_path.file_name() {
+            dialog = dialog.set_file_name(name.to_string_lossy());
+         }
+         if let Some(dir) = default_path.parent() {
+            dialog = dialog.set_directory(dir);
+         }
+
+         options.output_path = dialog.save_file();
+         options.output_path.is_some()
+      } else {
+         options.output_dir = self.file_dialog().pick_folder();
+         options.output_dir.is_some()
+      }
+   }
+
+   /// Where a decrypted copy of `path` goes by default in the current format
+   fn decrypted_path(&self, path: &Path) -> PathBuf {
+      if self.age {
+         age_format::decrypted_path(path)
+      } else {
+         decrypted_path(path)
+      }
+   }
+
+   /// A file dialog that opens in the last used directory
+   fn file_dialog(&self) -> rfd::FileDialog {
+      let dialog = rfd::FileDialog::new();
+      match &self.last_dir {
+         Some(dir) if dir.is_dir() => dialog.set_directory(dir),
+         _ => dialog,
+      }
+   }
+
+   fn remember_dir(&mut self, picked: Option<&PathBuf>) {
+      if let Some(dir) = picked.and_then(|path| path.parent()) {
+         self.last_dir = Some(dir.to_path_buf());
+      }
+   }
+
+   fn run(&mut self, action: Action, options: Options, inputs: Vec<PathBuf>) {
+      match action {
+         Action::Encrypt(argon2) => self.encrypt(argon2, options, inputs),
+         Action::Decrypt => self.decrypt(options, inputs),
+      }
+   }
+
+   fn show_overwrite_prompt(&mut self, theme: &Theme, ui: &mut Ui) {
+      let Some(prompt) = &self.overwrite_prompt else {
+         return;
+      };
+
+      let msg = if prompt.existing.len() == 1 {
+         format!("{} already exists", prompt.existing[0].display())
+      } else {
+         format!("{} of the outputs already exist", prompt.existing.len())
+      };
+
+      // None means cancel
+      let mut choice: Option<Option<Overwrite>> = None;
+      let mut open = true;
+
+      Modal::new("overwrite_prompt", &mut open)
+         .close_on_backdrop(false)
+         .close_on_escape(false)
+         .show(ui.ctx(), |ui| {
+            ui.set_width(320.0);
+
+            ui.vertical_centered(|ui| {
+               ui.spacing_mut().item_spacing.y = 20.0;
+               ui.spacing_mut().button_padding = vec2(10.0, 10.0);
+
+               ui.label(RichText::new(msg).size(theme.text_sizes.normal));
+
+               let size = vec2(ui.available_width(), 30.0);
+               ui.allocate_ui_with_layout(size, Layout::left_to_right(Align::Center), |ui| {
+                  ui.spacing_mut().item_spacing.x = 10.0;
+                  let visuals = theme.button_visuals();
+
+                  let button = Button::new(RichText::new("Overwrite").size(theme.text_sizes.normal)).visuals(visuals);
+                  if ui.add(button).clicked() {
+                     choice = Some(Some(Overwrite::Replace));
+                  }
+
+                  let button = Button::new(RichText::new("Keep both").size(theme.text_sizes.normal)).visuals(visuals);
+                  if ui.add(button).clicked() {
+                     choice = Some(Some(Overwrite::KeepBoth));
+                  }
+
+                  let button = Button::new(RichText::new("Cancel").size(theme.text_sizes.normal)).visuals(visuals);
+                  if ui.add(button).clicked() {
+                     choice = Some(None);
+                  }
                });
-            }
+            });
+         });
+
+      if let Some(choice) = choice {
+         let prompt = self.overwrite_prompt.take().unwrap();
+         if let Some(overwrite) = choice {
+            let options = Options {
+               overwrite,
+               ..prompt.options
+            };
+            self.run(prompt.action, options, prompt.inputs);
          }
+      }
+   }
+
+   /// Shows the format and Argon2 parameters of every queued file
+   fn inspect(&self) {
+      if self.running || self.queue.is_empty() {
+         return;
+      }
+
+      let paths: Vec<PathBuf> = self.queue.iter().map(|file| file.path.clone()).collect();
+
+      std::thread::spawn(move || {
+         let msg = paths
+            .iter()
+            .map(|path| {
+               let name = path
+                  .file_name()
+                  .unwrap_or(path.as_os_str())
+                  .to_string_lossy();
+
+               match inspect(path) {
+                  Ok(info) => {
+                     let mut text = format!("{}\n{}", name, info);
+                     if let Some(warning) = info.memory_warning() {
+                        text.push_str(&format!("\n\nWarning: {}", warning));
+                     }
+                     text
+                  }
+                  Err(e) => format!("{}\n{}", name, e),
+               }
+            })
+            .collect::<Vec<_>>()
+            .join("\n\n");
+
+         SHARED_GUI.write(|gui| {
+            gui.msg_window.open_with_msg(msg);
+         });
       });
    }
 
-   fn decrypt(&mut self) {
-      let file_path = self.file_path.clone();
-      let username = self.credentials_form.username();
-      let password = self.credentials_form.password();
-      let credentials = Credentials::new(username, password.clone(), password);
+   /// Looks for files below `minimum` in `dir` and queues them
+   fn scan(&mut self, dir: PathBuf, minimum: Argon2) {
+      self.running = true;
 
       std::thread::spawn(move || {
-         let encrypted_data = match std::fs::read(&file_path) {
-            Ok(data) => data,
-            Err(e) => {
-               SHARED_GUI.write(|gui| {
-                  gui.msg_window
-                     .open_with_msg(format!("Error reading file: {}", e));
-               });
-               return;
+         SHARED_GUI.write(|gui| {
+            gui.msg_window.open_with_loading("Scanning...");
+         });
+
+         let res = rekey::scan(&dir, &minimum);
+
+         SHARED_GUI.write(|gui| {
+            let file_encryption = &mut gui.file_encryption;
+            file_encryption.running = false;
+            match res {
+               Ok(report) => {
+                  for file in &report.weak {
+                     file_encryption.add_to_queue(file.path.clone());
+                  }
+                  let mut msg = report.message(&minimum);
+                  if !report.weak.is_empty() {
+                     msg.push_str("\n\nThe files were queued, enter their credentials and click Re-key");
+                  }
+                  gui.msg_window.open_with_msg(msg);
+               }
+               Err(e) => gui.msg_window.open_with_msg(e.to_string()),
             }
-         };
+         });
+      });
+   }
+
+   /// Re-keys every queued file with `argon2` and the credentials of the form
+   fn rekey(&mut self, argon2: Argon2) {
+      let unlock = self.unlock();
+      let options = Options {
+         ignore_memory_limit: self.ignore_memory_limit,
+         keyfile: !self.keyfiles.is_empty() && !self.recipients.enabled,
+         ..Default::default()
+      };
+      let Some(paths) = self.start_batch(self.queued_paths()) else {
+         return;
+      };
 
+      let progress = Progress::new();
+
+      std::thread::spawn(move || {
          SHARED_GUI.write(|gui| {
-            gui.msg_window.open_with_loading("Decrypting...");
+            gui.msg_window
+               .open_with_progress("Re-keying...", progress.clone());
          });
 
-         let mut decrypted_data = match decrypt_data_unsecured(encrypted_data, credentials) {
-            Ok(data) => data,
-            Err(e) => {
-               SHARED_GUI.write(|gui| {
-                  gui.msg_window
-                     .open_with_msg(format!("Error decrypting file: {}", e));
-               });
-               return;
+         let total = paths.len();
+         let res = unlock.and_then(|unlock| match unlock {
+            Unlock::Credentials(username, password, keyfiles) => keyfile::credentials(username, password, &keyfiles)
+               .map(|credentials| {
+                  rekey_batch(
+                     argon2,
+                     &paths,
+                     credentials,
+                     &options,
+                     &progress,
+                     |i, status| {
+                        update_status(&paths[i], i, total, status, "Re-keying", &progress);
+                     },
+                  )
+               }),
+            Unlock::Secret(_) => Err(Error::Decrypt(
+               "Re-keying needs the credentials, turn off public keys".to_string(),
+            )),
+         });
+
+         SHARED_GUI.write(|gui| {
+            gui.file_encryption.running = false;
+            match res {
+               Ok(summary) => gui.msg_window.open_with_msg(summary.message("re-keyed")),
+               Err(e) => gui.msg_window.open_with_msg(e.to_string()),
             }
-         };
+         });
+      });
+   }
 
-         // remove the extension
-         let new_file_path = file_path.replace(FILE_EXTENSION, "");
+   /// Opens the key slots of the single queued file
+   fn open_slots(&mut self) {
+      let Some(file) = self.queue.first() else {
+         return;
+      };
 
-         match std::fs::write(&new_file_path, &decrypted_data) {
-            Ok(_) => {
-               SHARED_GUI.write(|gui| {
-                  gui.msg_window
-                     .open_with_msg(format!("File decrypted successfully to {}", new_file_path));
-               });
+      self
+         .slots
+         .set_slots(slots::list(&file.path).map_err(|e| e.to_string()));
+      self.slots.path = Some(file.path.clone());
+      self.slots.open = true;
+   }
+
+   /// Writes `change` to the file the key slots are open for
+   fn change_slots(&mut self, argon2: Argon2, change: SlotChange) {
+      let Some(path) = self.slots.path.clone() else {
+         return;
+      };
+
+      let unlock = self.unlock();
+      let keyfiles = self.keyfiles.clone();
+      let new_argon2 = self.slots.new_argon2;
+      let options = Options {
+         ignore_memory_limit: self.ignore_memory_limit,
+         keyfile: !self.keyfiles.is_empty() && !self.recipients.enabled,
+         ..Default::default()
+      };
+      let progress = Progress::new();
+      self.slots.busy = true;
+
+      std::thread::spawn(move || {
+         SHARED_GUI.write(|gui| {
+            gui.msg_window
+               .open_with_progress("Updating key slots...", progress.clone());
+         });
+
+         let secret = unlock.and_then(|unlock| match unlock {
+            Unlock::Credentials(username, password, keyfiles) => {
+               keyfile::credentials(username, password, &keyfiles).map(Secret::from)
             }
-            Err(e) => {
-               SHARED_GUI.write(|gui| {
-                  gui.msg_window
-                     .open_with_msg(format!("Error writing file: {}", e));
-               });
+            Unlock::Secret(secret) => Ok(secret),
+         });
+
+         let res = secret.and_then(|secret| match change {
+            SlotChange::AddPassword(username, password) => keyfile::credentials(username, password, &keyfiles)
+               .and_then(|credentials| slots::add_password(&path, &secret, argon2, credentials, &options, &progress)),
+            SlotChange::AddRecipient(recipient) => {
+               slots::add_recipient(&path, &secret, &recipient, &options, &progress)
+            }
+            SlotChange::Remove(index) => slots::remove(&path, index, &secret, &options, &progress),
+            SlotChange::ChangePassword(username, password) => {
+               let Secret::Credentials(old) = secret else {
+                  return Err(Error::Decrypt(
+                     "Changing the password needs the current credentials, turn off public keys".to_string(),
+                  ));
+               };
+               keyfile::credentials(username, password, &keyfiles).and_then(|new| {
+                  rekey::change_credentials(
+                     &path,
+                     old,
+                     new_argon2.then_some(argon2),
+                     new,
+                     options.keyfile,
+                     &options,
+                     &progress,
+                  )
+               })
             }
+         });
+         let list = slots::list(&path).map_err(|e| e.to_string());
+
+         SHARED_GUI.write(|gui| {
+            let slots = &mut gui.file_encryption.slots;
+            slots.busy = false;
+            slots.set_slots(list);
+            match res {
+               Ok(()) => {
+                  slots.credentials_form.erase();
+                  slots.recipient_input.clear();
+                  gui.msg_window.open_with_msg("Key slots updated");
+               }
+               Err(e) => gui.msg_window.open_with_msg(e.to_string()),
+            }
+         });
+      });
+   }
+
+   fn queued_paths(&self) -> Vec<PathBuf> {
+      self.queue.iter().map(|file| file.path.clone()).collect()
+   }
+
+   /// Marks the files in `paths` as queued and returns them, or `None` if there's nothing to do
+   fn start_batch(&mut self, paths: Vec<PathBuf>) -> Option<Vec<PathBuf>> {
+      if self.running || paths.is_empty() {
+         return None;
+      }
+
+      self.running = true;
+      for file in self
+         .queue
+         .iter_mut()
+         .filter(|file| paths.contains(&file.path))
+      {
+         file.status = JobStatus::Queued;
+      }
+
+      Some(paths)
+   }
+
+   /// Who to encrypt for given the chosen format and whether public keys are used
+   fn target(&self) -> Result<Target, Error> {
+      if self.age && (self.recovery.split || self.recovery.create) {
+         return Err(Error::Encrypt(
+            "age files can't hold a recovery key, turn it off to use the age format".to_string(),
+         ));
+      }
+
+      let username = self.credentials_form.username();
+      let password = self.credentials_form.password();
+
+      match (self.age, self.recipients.enabled) {
+         (false, false) => Ok(Target::Credentials(
+            username,
+            password,
+            self.keyfiles.clone(),
+         )),
+         (false, true) => Ok(Target::Recipients(self.recipients.recipients.clone())),
+         (true, false) if !self.keyfiles.is_empty() => Err(Error::Keyfile(
+            "age files can't use keyfiles, clear them to use the age format".to_string(),
+         )),
+         (true, false) => Ok(Target::Age(AgeTarget::Passphrase(password))),
+         (true, true) => Ok(Target::Age(AgeTarget::Recipients(
+            self.recipients.age_recipients.clone(),
+         ))),
+      }
+   }
+
+   /// What to decrypt with given the chosen format and whether public keys are used
+   fn unlock(&self) -> Result<Unlock, Error> {
+      if self.recovery.recover {
+         if self.age {
+            return Err(Error::Decrypt(
+               "age files can't be opened with a recovery key, turn off the age format".to_string(),
+            ));
          }
+         return self
+            .recovery
+            .recovery_key()
+            .map(|key| Unlock::Secret(key.into()));
+      }
+
+      let username = self.credentials_form.username();
+      let password = self.credentials_form.password();
+
+      match (self.age, self.recipients.enabled) {
+         (false, false) => Ok(Unlock::Credentials(
+            username,
+            password,
+            self.keyfiles.clone(),
+         )),
+         (false, true) => match &self.recipients.identity {
+            Some(identity) => Ok(Unlock::Secret(identity.clone().into())),
+            None => Err(Error::Decrypt("Load your identity first".to_string())),
+         },
+         (true, false) if !self.keyfiles.is_empty() => Err(Error::Keyfile(
+            "age files can't use keyfiles, clear them to use the age format".to_string(),
+         )),
+         (true, false) => Ok(Unlock::Secret(AgeSecret::Passphrase(password).into())),
+         (true, true) if self.recipients.age_identities.is_empty() => Err(Error::Decrypt(
+            "Load your age identity file first".to_string(),
+         )),
+         (true, true) => Ok(Unlock::Secret(
+            AgeSecret::Identities(self.recipients.age_identities.clone()).into(),
+         )),
+      }
+   }
+
+   fn encrypt(&mut self, argon2: Argon2, options: Options, paths: Vec<PathBuf>) {
+      let target = self.target();
+      let split = self
+         .recovery
+         .split
+         .then_some((self.recovery.threshold, self.recovery.count));
+      let create = self.recovery.create;
+      let Some(paths) = self.start_batch(paths) else {
+         return;
+      };
 
-         decrypted_data.zeroize();
+      let progress = Progress::new();
+
+      std::thread::spawn(move || {
+         SHARED_GUI.write(|gui| {
+            gui.msg_window
+               .open_with_progress("Encrypting...", progress.clone());
+         });
+
+         let total = paths.len();
+         let on_status = |i: usize, status| {
+            update_status(&paths[i], i, total, status, "Encrypting", &progress);
+         };
+
+         let res = target.and_then(|target| {
+            let mut key = match target {
+               Target::Credentials(username, password, keyfiles) => keyfile::credentials(username, password, &keyfiles)
+                  .and_then(|credentials| derive_key(argon2, credentials, &options, &progress))?,
+               Target::Recipients(recipients) => DataKey::for_recipients(&recipients)?,
+               Target::Age(target) => {
+                  let summary = encrypt_batch_age(&target, &paths, &options, &progress, on_status);
+                  return Ok((summary, Vec::new(), None));
+               }
+            };
+
+            // One recovery key for the whole batch, just like the data key
+            let shares = match split {
+               Some((threshold, count)) => {
+                  let recovery_key = RecoveryKey::generate();
+                  key.add_recovery(&recovery_key)?;
+                  shamir::split(&recovery_key, threshold, count)?
+               }
+               None => Vec::new(),
+            };
+
+            let printable = if create {
+               let recovery_key = RecoveryKey::generate();
+               key.add_recovery(&recovery_key)?;
+               Some(recovery_key)
+            } else {
+               None
+            };
+
+            let summary = encrypt_batch_with_key(&key, &paths, &options, &progress, on_status);
+            Ok((summary, shares, printable))
+         });
+
+         SHARED_GUI.write(|gui| {
+            gui.file_encryption.running = false;
+            match res {
+               Ok((summary, shares, printable)) => {
+                  if !summary.succeeded.is_empty() {
+                     gui.file_encryption.recovery.shares = shares;
+                     gui.file_encryption.recovery.key = printable;
+                  }
+                  gui.msg_window.open_with_msg(summary.message("encrypted"));
+               }
+               Err(e) => gui.msg_window.open_with_msg(e.to_string()),
+            }
+         });
+      });
+   }
+
+   fn decrypt(&mut self, options: Options, paths: Vec<PathBuf>) {
+      let unlock = self.unlock();
+      let Some(paths) = self.start_batch(paths) else {
+         return;
+      };
+
+      let progress = Progress::new();
+
+      std::thread::spawn(move || {
+         SHARED_GUI.write(|gui| {
+            gui.msg_window
+               .open_with_progress("Decrypting...", progress.clone());
+         });
+
+         let total = paths.len();
+         let res = unlock
+            .and_then(|unlock| match unlock {
+               Unlock::Credentials(username, password, keyfiles) => {
+                  keyfile::credentials(username, password, &keyfiles).map(Secret::from)
+               }
+               Unlock::Secret(secret) => Ok(secret),
+            })
+            .map(|secret| {
+               decrypt_batch(&paths, secret, &options, &progress, |i, status| {
+                  update_status(&paths[i], i, total, status, "Decrypting", &progress);
+               })
+            });
+
+         SHARED_GUI.write(|gui| {
+            gui.file_encryption.running = false;
+            match res {
+               Ok(summary) => {
+                  let mut msg = summary.message("decrypted");
+
+                  // The outputs named after the stored names, ask what to do with them like before starting
+                  if !summary.existing.is_empty() && !summary.cancelled {
+                     msg.push_str("\n\nChoose what happens to the files that already exist");
+                     let (inputs, existing) = summary.existing.into_iter().unzip();
+                     gui.file_encryption.overwrite_prompt = Some(OverwritePrompt {
+                        action: Action::Decrypt,
+                        options,
+                        inputs,
+                        existing,
+                     });
+                  }
+                  gui.msg_window.open_with_msg(msg);
+               }
+               Err(e) => gui.msg_window.open_with_msg(e.to_string()),
+            }
+         });
       });
    }
 }
+
+/// Shows the status of the queued file at `path`, the `i`th of `total` in the batch
+fn update_status(path: &Path, i: usize, total: usize, status: JobStatus, action: &str, progress: &Progress) {
+   SHARED_GUI.write(|gui| {
+      if status == JobStatus::Running {
+         gui.msg_window.open_with_progress(
+            format!("{} {} of {}...", action, i + 1, total),
+            progress.clone(),
+         );
+      }
+      if let Some(file) = gui
+         .file_encryption
+         .queue
+         .iter_mut()
+         .find(|file| file.path == path)
+      {
+         file.status = status;
+      }
+   });
+}
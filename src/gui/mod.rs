@@ -1,9 +1,15 @@
-use eframe::egui::{Order, RichText, Slider, Spinner, Ui, vec2};
+use crate::core::{
+   Phase, Preset, Progress,
+   calibrate::{MAX_T_COST, MIN_M_COST, MIN_T_COST},
+   memory,
+};
+use eframe::egui::{Order, ProgressBar, RichText, Slider, Spinner, Ui, vec2};
 use lazy_static::lazy_static;
 use ncrypt_me::Argon2;
 use std::sync::{Arc, RwLock};
+use std::time::Duration;
 use zeus_theme::{Theme, ThemeKind};
-use zeus_widgets::{Button, Modal};
+use zeus_widgets::{Button, ComboBox, Label, Modal};
 
 lazy_static! {
    pub static ref SHARED_GUI: SharedGUI = SharedGUI::default();
@@ -11,33 +17,55 @@ lazy_static! {
 
 const M_COST_TIP: &str =
     "How much memory the Argon2 algorithm uses. Higher values are more secure but way slower, make sure the memory cost does not exceed your computer RAM.
-    You probably want to just increase the Memory cost to a sensible value 256mb - 1024mb as this is the most important parameter for security.";
+    You probably want to just increase the Memory cost to a sensible value 256 MiB - 1 GiB as this is the most important parameter for security.";
 
 const T_COST_TIP: &str =
    "The number of iterations the Argon2 algorithm will run. Higher values are more secure but slower.";
 
 const P_COST_TIP: &str = "You should probably leave this to 1.";
 
+/// The upper end of the parallelism slider
+const MAX_P_COST: u32 = 256;
+
+/// The upper end of the memory slider in KiB, 10 GiB
+const MAX_M_COST: u32 = 10_485_760;
+
+use calibration::CalibrationUi;
 use file_encryption::FileEncryptionUi;
+use settings::Settings;
 use text_hashing::TextHashingUi;
 
 pub mod app;
+pub mod calibration;
 pub mod file_encryption;
+pub mod recipients;
+pub mod recovery;
+pub mod settings;
+pub mod slots;
 pub mod text_hashing;
 
 pub struct MessageWindow {
    pub open: bool,
    pub message: String,
    pub loading: bool,
+   /// The job being waited on while loading, if it reports progress
+   pub progress: Option<Progress>,
    pub size: (f32, f32),
 }
 
+impl Default for MessageWindow {
+   fn default() -> Self {
+      Self::new()
+   }
+}
+
 impl MessageWindow {
    pub fn new() -> Self {
       Self {
          open: false,
          message: String::new(),
          loading: false,
+         progress: None,
          size: (250.0, 150.0),
       }
    }
@@ -45,6 +73,7 @@ impl MessageWindow {
    pub fn open_with_msg(&mut self, msg: impl Into<String>) {
       self.open = true;
       self.loading = false;
+      self.progress = None;
       self.message = msg.into();
    }
 
@@ -52,6 +81,15 @@ impl MessageWindow {
       self.open = true;
       self.message = msg.into();
       self.loading = true;
+      self.progress = None;
+   }
+
+   /// Like [Self::open_with_loading] but shows the progress of the job and lets the user cancel it
+   pub fn open_with_progress(&mut self, msg: impl Into<String>, progress: Progress) {
+      self.open = true;
+      self.message = msg.into();
+      self.loading = true;
+      self.progress = Some(progress);
    }
 
    pub fn show(&mut self, theme: &Theme, ui: &mut Ui) {
@@ -74,8 +112,13 @@ impl MessageWindow {
                ui.spacing_mut().button_padding = vec2(10.0, 10.0);
 
                if self.loading {
-                  ui.add(Spinner::new().size(20.0).color(theme.colors.text));
-                  ui.label(RichText::new(self.message.clone()).size(theme.text_sizes.normal));
+                  match &self.progress {
+                     Some(progress) => Self::show_progress(theme, progress, &self.message, ui),
+                     None => {
+                        ui.add(Spinner::new().size(20.0).color(theme.colors.text));
+                        ui.label(RichText::new(self.message.clone()).size(theme.text_sizes.normal));
+                     }
+                  }
                } else {
                   ui.spacing_mut().item_spacing.y = 20.0;
 
@@ -91,6 +134,75 @@ impl MessageWindow {
    }
 }
 
+impl MessageWindow {
+   fn show_progress(theme: &Theme, progress: &Progress, message: &str, ui: &mut Ui) {
+      ui.label(RichText::new(message).size(theme.text_sizes.normal));
+
+      let phase = progress.phase();
+      if progress.is_cancelled() {
+         ui.add(Spinner::new().size(20.0).color(theme.colors.text));
+         ui.label(RichText::new("Cancelling...").size(theme.text_sizes.small));
+         return;
+      }
+
+      match phase {
+         Phase::Encrypting | Phase::Decrypting | Phase::Verifying | Phase::Shredding if progress.bytes_total() > 0 => {
+            ui.add(ProgressBar::new(progress.fraction()).show_percentage());
+
+            let mut text = format!(
+               "{}: {} / {}",
+               phase,
+               format_bytes(progress.bytes_done()),
+               format_bytes(progress.bytes_total())
+            );
+            if let Some(eta) = progress.eta() {
+               text.push_str(&format!(", {} left", format_duration(eta)));
+            }
+            ui.label(RichText::new(text).size(theme.text_sizes.small));
+         }
+         _ => {
+            ui.add(Spinner::new().size(20.0).color(theme.colors.text));
+            ui.label(RichText::new(format!("{}...", phase)).size(theme.text_sizes.small));
+         }
+      }
+
+      let visuals = theme.button_visuals();
+      let button = Button::new(RichText::new("Cancel").size(theme.text_sizes.normal)).visuals(visuals);
+      if ui.add(button).clicked() {
+         progress.cancel();
+      }
+
+      // The worker thread doesn't wake up the UI on its own
+      ui.ctx().request_repaint_after(Duration::from_millis(100));
+   }
+}
+
+fn format_bytes(bytes: u64) -> String {
+   const UNITS: [&str; 4] = ["B", "KiB", "MiB", "GiB"];
+
+   let mut value = bytes as f64;
+   let mut unit = 0;
+   while value >= 1024.0 && unit < UNITS.len() - 1 {
+      value /= 1024.0;
+      unit += 1;
+   }
+
+   if unit == 0 {
+      format!("{} {}", bytes, UNITS[0])
+   } else {
+      format!("{:.1} {}", value, UNITS[unit])
+   }
+}
+
+fn format_duration(duration: Duration) -> String {
+   let secs = duration.as_secs();
+   if secs >= 60 {
+      format!("{}m {}s", secs / 60, secs % 60)
+   } else {
+      format!("{}s", secs)
+   }
+}
+
 #[derive(Clone)]
 pub struct SharedGUI(Arc<RwLock<GUI>>);
 
@@ -112,10 +224,17 @@ impl Default for SharedGUI {
 
 pub struct GUI {
    pub theme: Theme,
+   pub theme_kind: ThemeKind,
    pub file_encryption: FileEncryptionUi,
    pub text_hashing: TextHashingUi,
    pub argon2: Argon2,
+   pub preset: Preset,
+   pub calibration: CalibrationUi,
    pub msg_window: MessageWindow,
+   /// The upper end of the memory cost slider, from the physical memory read at startup
+   max_m_cost: u32,
+   /// The memory cost the warning below the slider was last checked for, and the warning
+   memory_warning: Option<(u32, Option<String>)>,
 }
 
 impl Default for GUI {
@@ -125,15 +244,54 @@ impl Default for GUI {
 
       Self {
          theme,
+         theme_kind: ThemeKind::TokyoNight,
          file_encryption: FileEncryptionUi::new(),
          text_hashing: TextHashingUi::new(),
+         preset: Preset::from_argon2(&argon2),
          argon2,
+         calibration: CalibrationUi::new(),
          msg_window: MessageWindow::new(),
+         // 64 MiB - 10 GiB, or less if the computer has less RAM
+         max_m_cost: memory::total()
+            .map(|total| (total / 1024).min(MAX_M_COST as u64) as u32)
+            .unwrap_or(MAX_M_COST)
+            .max(MIN_M_COST),
+         memory_warning: None,
       }
    }
 }
 
 impl GUI {
+   /// The current preferences worth keeping
+   pub fn settings(&self) -> Settings {
+      Settings {
+         theme: self.theme_kind.clone(),
+         m_cost: self.argon2.m_cost,
+         t_cost: self.argon2.t_cost,
+         p_cost: self.argon2.p_cost,
+         hash_algorithm: self.text_hashing.algorithm.clone(),
+         last_dir: self.file_encryption.last_dir.clone(),
+      }
+   }
+
+   /// Applies `settings` read from disk, values out of the range of the sliders are clamped to it
+   pub fn apply_settings(&mut self, settings: &Settings) {
+      // Custom themes can't be built from the kind alone
+      let kind = match &settings.theme {
+         ThemeKind::Custom => ThemeKind::TokyoNight,
+         kind if !ThemeKind::to_vec().contains(kind) => ThemeKind::TokyoNight,
+         kind => kind.clone(),
+      };
+      self.theme = Theme::new(kind.clone());
+      self.theme_kind = kind;
+      self.argon2.m_cost = settings.m_cost.clamp(MIN_M_COST, self.max_m_cost);
+      self.argon2.t_cost = settings.t_cost.clamp(MIN_T_COST, MAX_T_COST);
+      self.argon2.p_cost = settings.p_cost.clamp(1, MAX_P_COST);
+      self.preset = Preset::from_argon2(&self.argon2);
+      self.text_hashing.algorithm = settings.hash_algorithm.clone();
+      self.file_encryption.last_dir = settings.last_dir.clone();
+   }
+
    pub fn show_left_panel(&mut self, ui: &mut Ui) {
       ui.vertical(|ui| {
          ui.spacing_mut().item_spacing.y = 20.0;
@@ -158,9 +316,36 @@ impl GUI {
                self.text_hashing.open = true;
             }
          });
+
+         self.select_theme(ui);
       });
    }
 
+   fn select_theme(&mut self, ui: &mut Ui) {
+      let theme = &self.theme;
+      let label_text = RichText::new(self.theme_kind.to_str()).size(theme.text_sizes.normal);
+      let label = Label::new(label_text, None);
+
+      let mut selected = self.theme_kind.clone();
+      ComboBox::new("select_theme", label)
+         .visuals(theme.combo_box_visuals())
+         .width(110.0)
+         .show_ui(ui, |ui| {
+            ui.spacing_mut().button_padding = vec2(5.0, 5.0);
+
+            for kind in ThemeKind::to_vec() {
+               let text = RichText::new(kind.to_str()).size(theme.text_sizes.normal);
+               ui.selectable_value(&mut selected, kind, text);
+            }
+         });
+
+      if selected != self.theme_kind {
+         self.theme = Theme::new(selected.clone());
+         self.theme_kind = selected;
+         ui.ctx().set_global_style(self.theme.style.clone());
+      }
+   }
+
    pub fn show_right_panel(&mut self, ui: &mut Ui) {
       // Argon Params
 
@@ -170,26 +355,101 @@ impl GUI {
 
          ui.label(RichText::new("Argon2 Parameters").size(self.theme.text_sizes.normal));
 
-         ui.label(RichText::new("Memory cost (MB):").size(self.theme.text_sizes.normal))
+         self.select_preset(ui);
+
+         ui.label(RichText::new("Memory cost:").size(self.theme.text_sizes.normal))
             .on_hover_text(M_COST_TIP);
 
-         ui.add(
-            Slider::new(&mut self.argon2.m_cost, 64_000..=10000000) // 64MB - 10GB
-               .custom_formatter(|v, _ctx| format!("{:.0} MB", v / 1000.0)),
+         let m_cost = ui.add(
+            Slider::new(&mut self.argon2.m_cost, MIN_M_COST..=self.max_m_cost)
+               .custom_formatter(|v, _ctx| memory::format_kib(v as u64)),
          );
 
+         if let Some(warning) = self.memory_warning() {
+            ui.label(RichText::new(warning).size(self.theme.text_sizes.small));
+         }
+
          ui.label(RichText::new("Iterations:").size(self.theme.text_sizes.normal))
             .on_hover_text(T_COST_TIP);
 
-         ui.add(Slider::new(&mut self.argon2.t_cost, 5..=1024));
+         let t_cost = ui.add(Slider::new(
+            &mut self.argon2.t_cost,
+            MIN_T_COST..=MAX_T_COST,
+         ));
 
          ui.label(RichText::new("Parallelism:").size(self.theme.text_sizes.normal))
             .on_hover_text(P_COST_TIP);
 
-         ui.add(Slider::new(&mut self.argon2.p_cost, 1..=256));
+         let p_cost = ui.add(Slider::new(&mut self.argon2.p_cost, 1..=MAX_P_COST));
+
+         if m_cost.changed() || t_cost.changed() || p_cost.changed() {
+            self.preset = Preset::Custom;
+         }
+
+         self.calibration.show(&self.theme, self.max_m_cost, ui);
       });
    }
 
+   /// Why the memory cost is too high, only checked again once it changes
+   fn memory_warning(&mut self) -> Option<String> {
+      let m_cost = self.argon2.m_cost;
+      match &self.memory_warning {
+         Some((checked, warning)) if *checked == m_cost => warning.clone(),
+         _ => {
+            let warning = memory::check(&self.argon2).err().map(|e| e.to_string());
+            self.memory_warning = Some((m_cost, warning.clone()));
+            warning
+         }
+      }
+   }
+
+   fn select_preset(&mut self, ui: &mut Ui) {
+      let theme = &self.theme;
+      let label_text = RichText::new(self.preset.to_string()).size(theme.text_sizes.normal);
+      let label = Label::new(label_text, None);
+
+      // Computed up front as the combo box borrows the preset
+      let entries: Vec<(Preset, String)> = Preset::ALL
+         .into_iter()
+         .map(|preset| {
+            let text = match preset.argon2() {
+               Some(argon2) => {
+                  let mut text = format!("{} ({}", preset, memory::format_kib(argon2.m_cost as u64));
+                  if let Some(estimate) = self.calibration.estimate(&argon2) {
+                     text.push_str(&format!(", ~{:.1} s", estimate.as_secs_f64()));
+                  }
+                  text + ")"
+               }
+               None => preset.to_string(),
+            };
+            (preset, text)
+         })
+         .collect();
+
+      let mut selected = self.preset;
+      ComboBox::new("select_preset", label)
+         .visuals(theme.combo_box_visuals())
+         .width(150.0)
+         .show_ui(ui, |ui| {
+            ui.spacing_mut().button_padding = vec2(5.0, 5.0);
+
+            for (preset, text) in entries {
+               ui.selectable_value(
+                  &mut selected,
+                  preset,
+                  RichText::new(text).size(theme.text_sizes.normal),
+               );
+            }
+         });
+
+      if selected != self.preset {
+         self.preset = selected;
+         if let Some(argon2) = selected.argon2() {
+            self.argon2 = argon2;
+         }
+      }
+   }
+
    pub fn show_central_panel(&mut self, ui: &mut Ui) {
       self.msg_window.show(&self.theme, ui);
       self
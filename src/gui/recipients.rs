@@ -0,0 +1,309 @@
+use super::SHARED_GUI;
+use crate::core::{
+   Identity, Overwrite, Recipient, age_format,
+   keyfile::{self, Keyfile},
+};
+use age::x25519;
+use eframe::egui::{Align, FontId, Layout, RichText, TextEdit, Ui, vec2};
+use ncrypt_me::{Argon2, secure_types::SecureString};
+use std::path::PathBuf;
+use zeus_theme::Theme;
+use zeus_widgets::Button;
+
+/// Encrypting to public keys and decrypting with a local identity instead of the credentials
+///
+/// The credentials entered above only protect the identity file itself in this mode.
+pub struct RecipientsUi {
+   pub enabled: bool,
+   /// The public keys files get encrypted to
+   pub recipients: Vec<Recipient>,
+   /// The `age1...` public keys age files get encrypted to
+   pub age_recipients: Vec<x25519::Recipient>,
+   /// The public key being typed in
+   pub input: String,
+   /// Why the typed in key was rejected
+   pub input_error: Option<String>,
+   /// The identity files get decrypted with
+   pub identity: Option<Identity>,
+   /// The identities age files get decrypted with, read from an `age-keygen` identity file
+   pub age_identities: Vec<x25519::Identity>,
+   /// Whether an identity is being generated or loaded
+   pub busy: bool,
+}
+
+impl Default for RecipientsUi {
+   fn default() -> Self {
+      Self::new()
+   }
+}
+
+impl RecipientsUi {
+   pub fn new() -> Self {
+      Self {
+         enabled: false,
+         recipients: Vec::new(),
+         age_recipients: Vec::new(),
+         input: String::new(),
+         input_error: None,
+         identity: None,
+         age_identities: Vec::new(),
+         busy: false,
+      }
+   }
+
+   /// `credentials` are what the identity file is protected with
+   pub fn show(
+      &mut self,
+      theme: &Theme,
+      argon2: Argon2,
+      credentials: impl Fn() -> (SecureString, SecureString, Vec<Keyfile>),
+      ui: &mut Ui,
+   ) {
+      let text = RichText::new("Use public keys").size(theme.text_sizes.normal);
+      ui.checkbox(&mut self.enabled, text).on_hover_text(
+         "Encrypt to the public keys of the recipients and decrypt with your identity, the credentials above only unlock the identity file",
+      );
+
+      if !self.enabled {
+         return;
+      }
+
+      self.show_recipients(theme, ui);
+
+      let size = vec2(ui.available_width() * 0.5, 30.0);
+      ui.allocate_ui_with_layout(size, Layout::left_to_right(Align::Center), |ui| {
+         ui.spacing_mut().item_spacing.x = 20.0;
+
+         let text = RichText::new("New Identity").size(theme.text_sizes.normal);
+         let button = Button::new(text).visuals(theme.button_visuals());
+         let clicked = ui
+            .add_enabled(!self.busy, button)
+            .on_hover_text("Generate a key pair and save it encrypted with the credentials above")
+            .clicked();
+
+         if clicked
+            && let Some(path) = rfd::FileDialog::new()
+               .set_file_name("identity.ncrypt")
+               .save_file()
+         {
+            self.generate(path, argon2.clone(), credentials());
+         }
+
+         let text = RichText::new("Load Identity").size(theme.text_sizes.normal);
+         let button = Button::new(text).visuals(theme.button_visuals());
+         let clicked = ui
+            .add_enabled(!self.busy, button)
+            .on_hover_text("Unlock an identity file with the credentials above, age identity files need no credentials")
+            .clicked();
+
+         if clicked && let Some(path) = rfd::FileDialog::new().pick_file() {
+            self.load(path, credentials());
+         }
+
+         let mut public_keys: Vec<String> = self
+            .identity
+            .iter()
+            .map(|identity| identity.recipient().to_string())
+            .collect();
+         public_keys.extend(
+            self
+               .age_identities
+               .iter()
+               .map(|identity| identity.to_public().to_string()),
+         );
+
+         if !public_keys.is_empty() {
+            let text = RichText::new("Copy Public Key").size(theme.text_sizes.normal);
+            let button = Button::new(text).visuals(theme.button_visuals());
+            if ui
+               .add(button)
+               .on_hover_text("Share this with whoever sends you files")
+               .clicked()
+            {
+               ui.ctx().copy_text(public_keys.join("\n"));
+            }
+         }
+      });
+   }
+
+   fn show_recipients(&mut self, theme: &Theme, ui: &mut Ui) {
+      let size = vec2(ui.available_width() * 0.5, 30.0);
+      ui.allocate_ui_with_layout(size, Layout::left_to_right(Align::Center), |ui| {
+         ui.spacing_mut().item_spacing.x = 20.0;
+
+         let text_edit = TextEdit::singleline(&mut self.input)
+            .hint_text("ncryptpk... or age1...")
+            .desired_width(ui.available_width() * 0.7)
+            .font(FontId::proportional(theme.text_sizes.normal));
+         ui.add(text_edit);
+
+         let text = RichText::new("Add Recipient").size(theme.text_sizes.normal);
+         let button = Button::new(text).visuals(theme.button_visuals());
+         if ui.add(button).clicked() {
+            self.add_recipient();
+         }
+      });
+
+      if let Some(e) = &self.input_error {
+         ui.label(RichText::new(e).size(theme.text_sizes.small));
+      }
+
+      let keys: Vec<String> = self
+         .recipients
+         .iter()
+         .map(|recipient| recipient.to_string())
+         .chain(
+            self
+               .age_recipients
+               .iter()
+               .map(|recipient| recipient.to_string()),
+         )
+         .collect();
+
+      let mut remove = None;
+      for (i, key) in keys.iter().enumerate() {
+         ui.horizontal(|ui| {
+            let short = format!("{}...{}", &key[..16], &key[key.len() - 8..]);
+            ui.label(RichText::new(short).size(theme.text_sizes.small))
+               .on_hover_text(key);
+
+            let text = RichText::new("x").size(theme.text_sizes.small);
+            let button = Button::new(text).visuals(theme.button_visuals());
+            if ui.add(button).clicked() {
+               remove = Some(i);
+            }
+         });
+      }
+
+      if let Some(i) = remove {
+         if i < self.recipients.len() {
+            self.recipients.remove(i);
+         } else {
+            self.age_recipients.remove(i - self.recipients.len());
+         }
+      }
+   }
+
+   /// Adds the typed in key to the nCrypt or the age recipients, depending on what it looks like
+   fn add_recipient(&mut self) {
+      let input = self.input.trim();
+
+      if input.starts_with("age1") {
+         match input.parse::<x25519::Recipient>() {
+            Ok(recipient) => {
+               if !self
+                  .age_recipients
+                  .iter()
+                  .any(|r| r.to_string() == recipient.to_string())
+               {
+                  self.age_recipients.push(recipient);
+               }
+            }
+            Err(e) => {
+               self.input_error = Some(format!("Not a valid age public key: {}", e));
+               return;
+            }
+         }
+      } else {
+         match input.parse::<Recipient>() {
+            Ok(recipient) => {
+               if !self.recipients.contains(&recipient) {
+                  self.recipients.push(recipient);
+               }
+            }
+            Err(e) => {
+               self.input_error = Some(e.to_string());
+               return;
+            }
+         }
+      }
+
+      self.input.clear();
+      self.input_error = None;
+   }
+
+   fn generate(&mut self, path: PathBuf, argon2: Argon2, credentials: (SecureString, SecureString, Vec<Keyfile>)) {
+      self.busy = true;
+
+      std::thread::spawn(move || {
+         SHARED_GUI.write(|gui| {
+            gui.msg_window.open_with_loading("Generating identity...");
+         });
+
+         let (username, password, keyfiles) = credentials;
+         let identity = Identity::generate();
+         let res = keyfile::credentials(username, password, &keyfiles)
+            .and_then(|credentials| identity.save(&path, argon2, credentials, Overwrite::Never));
+
+         SHARED_GUI.write(|gui| {
+            let recipients = &mut gui.file_encryption.recipients;
+            recipients.busy = false;
+            match res {
+               Ok(path) => {
+                  gui.msg_window.open_with_msg(format!(
+                     "Identity saved to {}\n\nYour public key is\n{}",
+                     path.display(),
+                     identity.recipient()
+                  ));
+                  recipients.identity = Some(identity);
+               }
+               Err(e) => gui.msg_window.open_with_msg(e.to_string()),
+            }
+         });
+      });
+   }
+
+   fn load(&mut self, path: PathBuf, credentials: (SecureString, SecureString, Vec<Keyfile>)) {
+      self.busy = true;
+
+      std::thread::spawn(move || {
+         SHARED_GUI.write(|gui| {
+            gui.msg_window.open_with_loading("Unlocking identity...");
+         });
+
+         // age identity files are plain text
+         if age_format::is_identity_file(&path) {
+            let res = age_format::read_identities(&path);
+
+            SHARED_GUI.write(|gui| {
+               let recipients = &mut gui.file_encryption.recipients;
+               recipients.busy = false;
+               match res {
+                  Ok(identities) => {
+                     let keys: Vec<String> = identities
+                        .iter()
+                        .map(|identity| identity.to_public().to_string())
+                        .collect();
+                     gui.msg_window.open_with_msg(format!(
+                        "age identity loaded, your public key is\n{}",
+                        keys.join("\n")
+                     ));
+                     recipients.age_identities = identities;
+                  }
+                  Err(e) => gui.msg_window.open_with_msg(e.to_string()),
+               }
+            });
+            return;
+         }
+
+         let (username, password, keyfiles) = credentials;
+         let res = keyfile::credentials(username, password, &keyfiles)
+            .and_then(|credentials| Identity::load(&path, credentials));
+
+         SHARED_GUI.write(|gui| {
+            let recipients = &mut gui.file_encryption.recipients;
+            recipients.busy = false;
+            match res {
+               Ok(identity) => {
+                  gui.msg_window.open_with_msg(format!(
+                     "Identity loaded, your public key is\n{}",
+                     identity.recipient()
+                  ));
+                  recipients.identity = Some(identity);
+               }
+               Err(e) => gui.msg_window.open_with_msg(e.to_string()),
+            }
+         });
+      });
+   }
+}
@@ -0,0 +1,427 @@
+use crate::core::{
+   Error, Overwrite, RecoveryKey,
+   output::write_file,
+   qr,
+   shamir::{self, SHARE_PREFIX, Share},
+};
+use eframe::egui::{Color32, FontId, Margin, Rect, RichText, ScrollArea, Sense, Slider, Ui, vec2};
+use ncrypt_me::secure_types::SecureString;
+use std::io::Write;
+use zeroize::Zeroizing;
+use zeus_theme::Theme;
+#[cfg(target_os = "linux")]
+use zeus_ui_components::QRScanner;
+use zeus_widgets::{Button, Modal, SecureTextEdit};
+
+/// The side of a QR code on screen
+const QR_SIZE: f32 = 180.0;
+
+/// Recovery keys to print or split among trustees when encrypting, and to decrypt with instead of the credentials
+pub struct RecoveryUi {
+   /// Add a recovery key to print to the encrypted files
+   pub create: bool,
+   /// The printable recovery key of the last encryption, shown once and then forgotten
+   pub key: Option<RecoveryKey>,
+   /// Add a recovery key split into shares to the encrypted files
+   pub split: bool,
+   /// The shares needed to recover the key
+   pub threshold: u8,
+   /// The shares handed out
+   pub count: u8,
+   /// The shares of the last encryption, shown once and then forgotten
+   pub shares: Vec<Share>,
+   /// Why saving a QR code failed
+   pub save_error: Option<String>,
+   /// Decrypt with a recovery key instead of the credentials
+   pub recover: bool,
+   /// The words of a recovery key or its shares one per line, typed, pasted or scanned in
+   pub input: SecureString,
+   /// Why reading a QR code picture failed
+   pub scan_error: Option<String>,
+   #[cfg(target_os = "linux")]
+   pub scanner: QRScanner,
+}
+
+impl Default for RecoveryUi {
+   fn default() -> Self {
+      Self::new()
+   }
+}
+
+impl RecoveryUi {
+   pub fn new() -> Self {
+      Self {
+         create: false,
+         key: None,
+         split: false,
+         threshold: 2,
+         count: 3,
+         shares: Vec::new(),
+         save_error: None,
+         recover: false,
+         input: SecureString::new_with_capacity(4096).unwrap(),
+         scan_error: None,
+         #[cfg(target_os = "linux")]
+         scanner: QRScanner::new(),
+      }
+   }
+
+   pub fn show(&mut self, theme: &Theme, ui: &mut Ui) {
+      let text = RichText::new("Create a printable recovery key").size(theme.text_sizes.normal);
+      ui.checkbox(&mut self.create, text).on_hover_text(
+         "When encrypting, also let a random recovery key open the files, it is shown once as words and a QR code to print and keep safe",
+      );
+
+      let text = RichText::new("Split a recovery key into shares").size(theme.text_sizes.normal);
+      ui.checkbox(&mut self.split, text).on_hover_text(
+         "When encrypting, also let a random recovery key open the files and split it among trustees, any of them holding enough shares can decrypt together",
+      );
+
+      if self.split {
+         ui.horizontal(|ui| {
+            ui.label(RichText::new("Shares needed").size(theme.text_sizes.normal));
+            ui.add(Slider::new(&mut self.threshold, 2..=self.count.max(2)));
+            ui.label(RichText::new("of").size(theme.text_sizes.normal));
+            ui.add(Slider::new(&mut self.count, 2..=16));
+         });
+         self.threshold = self.threshold.min(self.count);
+      }
+
+      let text = RichText::new("Use a recovery key").size(theme.text_sizes.normal);
+      ui.checkbox(&mut self.recover, text)
+         .on_hover_text("Decrypt with a recovery key or the shares of one instead of the credentials");
+
+      if !self.recover {
+         return;
+      }
+
+      ui.label(
+         RichText::new("Enter the words of the recovery key, or one share per line").size(theme.text_sizes.small),
+      );
+
+      let visuals = theme.text_edit_visuals();
+      self.input.secure_mut(|input| {
+         let text_edit = SecureTextEdit::multiline(input)
+            .visuals(visuals)
+            .desired_width(ui.available_width() * 0.5)
+            .desired_rows(4)
+            .margin(Margin::same(10))
+            .font(FontId::proportional(theme.text_sizes.small));
+         text_edit.show(ui);
+      });
+
+      ui.horizontal(|ui| {
+         let button =
+            Button::new(RichText::new("QR Picture").size(theme.text_sizes.small)).visuals(theme.button_visuals());
+         if ui
+            .add(button)
+            .on_hover_text("Add the recovery key or shares in the QR codes of a picture")
+            .clicked()
+         {
+            self.scan_picture();
+         }
+
+         #[cfg(target_os = "linux")]
+         self.scan_screen(theme, ui);
+      });
+
+      let status = match &self.scan_error {
+         Some(e) => e.clone(),
+         None => self.status(),
+      };
+      ui.label(RichText::new(status).size(theme.text_sizes.small));
+   }
+
+   /// Adds the text of the QR codes in a picture to the input
+   fn scan_picture(&mut self) {
+      match scan_qr() {
+         Ok(texts) => {
+            self.scan_error = None;
+            for text in &texts {
+               self.add_line(text);
+            }
+         }
+         Err(e) => self.scan_error = Some(e.to_string()),
+      }
+   }
+
+   /// Adds the recovery key or share in a QR code on the screen to the input
+   #[cfg(target_os = "linux")]
+   fn scan_screen(&mut self, theme: &Theme, ui: &mut Ui) {
+      let button = Button::new(RichText::new("Scan QR").size(theme.text_sizes.small)).visuals(theme.button_visuals());
+      if ui
+         .add(button)
+         .on_hover_text("Point the mouse at a QR code on the screen, e.g. a photo of a printed sheet")
+         .clicked()
+      {
+         self.scanner.open(ui.ctx().clone());
+      }
+
+      self.scanner.show(ui.ctx());
+      let Some(text) = self.scanner.get_result() else {
+         return;
+      };
+      self.scanner.reset();
+
+      self.scan_error = None;
+      text.unlock_str(|text| self.add_line(text));
+   }
+
+   /// Appends `text` to the input on a line of its own
+   fn add_line(&mut self, text: &str) {
+      let new_line = self
+         .input
+         .unlock_str(|input| !input.is_empty() && !input.ends_with('\n'));
+      if new_line {
+         self.input.push_str("\n");
+      }
+      self.input.push_str(text.trim());
+   }
+
+   /// The recovery key entered, or the one the entered shares put back together
+   pub fn recovery_key(&self) -> Result<RecoveryKey, Error> {
+      if self.has_shares() {
+         return shamir::combine(&self.parse_shares()?);
+      }
+
+      let mut key = Err(Error::Decrypt("No recovery key given".to_string()));
+      self.input.unlock_str(|input| {
+         key = RecoveryKey::from_words(input);
+      });
+      key
+   }
+
+   /// What is wrong with the input so far, if anything
+   fn status(&self) -> String {
+      let mut empty = true;
+      self
+         .input
+         .unlock_str(|input| empty = input.trim().is_empty());
+      if empty {
+         return "No recovery key yet".to_string();
+      }
+
+      if !self.has_shares() {
+         return match self.recovery_key() {
+            Ok(_) => "Valid recovery key".to_string(),
+            Err(e) => e.to_string(),
+         };
+      }
+
+      match self.parse_shares() {
+         Ok(shares) => format!("{} of {} shares", shares.len(), shares[0].threshold()),
+         Err(e) => e.to_string(),
+      }
+   }
+
+   fn has_shares(&self) -> bool {
+      let mut has_shares = false;
+      self.input.unlock_str(|input| {
+         has_shares = input.trim_start().starts_with(SHARE_PREFIX);
+      });
+      has_shares
+   }
+
+   fn parse_shares(&self) -> Result<Vec<Share>, Error> {
+      let mut shares = Ok(Vec::new());
+      self.input.unlock_str(|input| {
+         shares = input
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(|line| line.parse::<Share>())
+            .collect::<Result<Vec<_>, _>>();
+      });
+      shares
+   }
+
+   /// Shows the shares of the last encryption until the user closes them
+   pub fn show_shares(&mut self, theme: &Theme, ui: &mut Ui) {
+      if self.shares.is_empty() {
+         return;
+      }
+
+      let mut open = true;
+
+      Modal::new("recovery_shares", &mut open)
+         .close_on_backdrop(false)
+         .close_on_escape(false)
+         .show(ui.ctx(), |ui| {
+            ui.set_width(420.0);
+
+            ui.vertical_centered(|ui| {
+               ui.spacing_mut().item_spacing.y = 15.0;
+               ui.spacing_mut().button_padding = vec2(10.0, 8.0);
+
+               ui.label(RichText::new("Recovery Shares").size(theme.text_sizes.large));
+               let text = format!(
+                  "Hand one share to every trustee, any {} of them open the files together. The shares are not shown again.",
+                  self.shares[0].threshold()
+               );
+               ui.label(RichText::new(text).size(theme.text_sizes.normal));
+
+               ScrollArea::vertical().max_height(500.0).show(ui, |ui| {
+                  for (i, share) in self.shares.iter().enumerate() {
+                     let text = share.to_string();
+                     ui.label(RichText::new(format!("Share {} of {}", i + 1, self.shares.len())).size(theme.text_sizes.normal));
+                     show_qr(&text, ui);
+                     ui.label(RichText::new(&text).size(theme.text_sizes.small));
+
+                     ui.horizontal(|ui| {
+                        let button = Button::new(RichText::new("Copy").size(theme.text_sizes.small)).visuals(theme.button_visuals());
+                        if ui.add(button).clicked() {
+                           ui.ctx().copy_text(text.clone());
+                        }
+
+                        let button = Button::new(RichText::new("Save QR").size(theme.text_sizes.small)).visuals(theme.button_visuals());
+                        if ui.add(button).clicked() {
+                           let res = qr::to_svg(&text).and_then(|svg| save_svg(&svg, &format!("share-{}.svg", share.index())));
+                           self.save_error = res.err().map(|e| e.to_string());
+                        }
+                     });
+                     ui.add_space(10.0);
+                  }
+               });
+
+               if let Some(e) = &self.save_error {
+                  ui.label(RichText::new(e).size(theme.text_sizes.small));
+               }
+
+               let button = Button::new(RichText::new("Done").size(theme.text_sizes.normal)).visuals(theme.button_visuals());
+               if ui.add(button).clicked() {
+                  self.shares.clear();
+                  self.save_error = None;
+               }
+            });
+         });
+   }
+
+   /// Shows the printable recovery key of the last encryption until the user closes it, after any shares
+   pub fn show_key(&mut self, theme: &Theme, ui: &mut Ui) {
+      if !self.shares.is_empty() {
+         return;
+      }
+      let Some(key) = &self.key else {
+         return;
+      };
+
+      let words = key.to_words();
+      let mut done = false;
+      let mut open = true;
+
+      Modal::new("recovery_key", &mut open)
+         .close_on_backdrop(false)
+         .close_on_escape(false)
+         .show(ui.ctx(), |ui| {
+            ui.set_width(420.0);
+
+            ui.vertical_centered(|ui| {
+               ui.spacing_mut().item_spacing.y = 15.0;
+               ui.spacing_mut().button_padding = vec2(10.0, 8.0);
+
+               ui.label(RichText::new("Recovery Key").size(theme.text_sizes.large));
+               ui.label(
+                  RichText::new(
+                     "Print these words or write them down and keep them safe, they open the files without the password. They are not shown again.",
+                  )
+                  .size(theme.text_sizes.normal),
+               );
+
+               show_qr(&words, ui);
+
+               // 4 words per line, numbered so none get skipped when copied by hand
+               let lines: Vec<String> = words
+                  .split(' ')
+                  .enumerate()
+                  .map(|(i, word)| format!("{}. {}", i + 1, word))
+                  .collect::<Vec<_>>()
+                  .chunks(4)
+                  .map(|line| line.join("   "))
+                  .collect();
+               ui.label(RichText::new(lines.join("\n")).size(theme.text_sizes.normal));
+
+               ui.horizontal(|ui| {
+                  let button = Button::new(RichText::new("Copy").size(theme.text_sizes.small)).visuals(theme.button_visuals());
+                  if ui.add(button).clicked() {
+                     ui.ctx().copy_text(words.as_str().to_owned());
+                  }
+
+                  let button =
+                     Button::new(RichText::new("Save Sheet").size(theme.text_sizes.small)).visuals(theme.button_visuals());
+                  if ui.add(button).clicked() {
+                     let res = key.sheet().and_then(|sheet| save_svg(&sheet, "recovery-key.svg"));
+                     self.save_error = res.err().map(|e| e.to_string());
+                  }
+               });
+
+               if let Some(e) = &self.save_error {
+                  ui.label(RichText::new(e).size(theme.text_sizes.small));
+               }
+
+               let button = Button::new(RichText::new("Done").size(theme.text_sizes.normal)).visuals(theme.button_visuals());
+               if ui.add(button).clicked() {
+                  done = true;
+               }
+            });
+         });
+
+      if done {
+         self.key = None;
+         self.save_error = None;
+      }
+   }
+}
+
+/// Paints the QR code of `text`, or says why there is none
+pub fn show_qr(text: &str, ui: &mut Ui) {
+   let (width, modules) = match qr::modules(text) {
+      Ok(modules) => modules,
+      Err(e) => {
+         ui.label(e.to_string());
+         return;
+      }
+   };
+
+   // A quiet zone of 2 modules around the code
+   let module = QR_SIZE / (width + 4) as f32;
+   let (rect, _) = ui.allocate_exact_size(vec2(QR_SIZE, QR_SIZE), Sense::hover());
+   let painter = ui.painter_at(rect);
+   painter.rect_filled(rect, 0.0, Color32::WHITE);
+
+   for (i, dark) in modules.iter().enumerate() {
+      if !dark {
+         continue;
+      }
+      let (x, y) = ((i % width + 2) as f32, (i / width + 2) as f32);
+      let min = rect.min + vec2(x * module, y * module);
+      painter.rect_filled(
+         Rect::from_min_size(min, vec2(module, module)),
+         0.0,
+         Color32::BLACK,
+      );
+   }
+}
+
+/// Asks for a picture and reads the QR codes in it, nothing if no picture was picked
+pub fn scan_qr() -> Result<Vec<Zeroizing<String>>, Error> {
+   let Some(path) = rfd::FileDialog::new()
+      .add_filter("Images", &["png", "jpg", "jpeg"])
+      .pick_file()
+   else {
+      return Ok(Vec::new());
+   };
+
+   qr::scan(&path)
+}
+
+/// Asks where to save the SVG image `svg`
+pub fn save_svg(svg: &str, file_name: &str) -> Result<(), Error> {
+   let Some(path) = rfd::FileDialog::new().set_file_name(file_name).save_file() else {
+      return Ok(());
+   };
+
+   write_file(&path, Overwrite::Replace, |writer| {
+      writer.write_all(svg.as_bytes())?;
+      Ok(())
+   })?;
+   Ok(())
+}
@@ -0,0 +1,82 @@
+use super::text_hashing::HashAlgorithm;
+use crate::core::{Error, Overwrite, output::write_file};
+use serde::{Deserialize, Serialize};
+use std::io::Write;
+use std::path::PathBuf;
+use zeus_theme::ThemeKind;
+
+const FILE_NAME: &str = "settings.toml";
+
+/// Preferences kept between sessions
+///
+/// This is written to disk in plain text, so it must never hold credentials or anything derived from them.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+   pub theme: ThemeKind,
+   /// Argon2 memory cost in KiB
+   pub m_cost: u32,
+   pub t_cost: u32,
+   pub p_cost: u32,
+   pub hash_algorithm: HashAlgorithm,
+   /// Where the file dialogs open
+   pub last_dir: Option<PathBuf>,
+}
+
+impl Default for Settings {
+   fn default() -> Self {
+      let argon2 = ncrypt_me::Argon2::balanced();
+      Self {
+         theme: ThemeKind::TokyoNight,
+         m_cost: argon2.m_cost,
+         t_cost: argon2.t_cost,
+         p_cost: argon2.p_cost,
+         hash_algorithm: HashAlgorithm::Sha3_224,
+         last_dir: None,
+      }
+   }
+}
+
+impl Settings {
+   /// Loads the settings file, anything missing or unreadable falls back to the defaults
+   pub fn load() -> Self {
+      config_path()
+         .and_then(|path| std::fs::read_to_string(path).ok())
+         .and_then(|contents| toml::from_str(&contents).ok())
+         .unwrap_or_default()
+   }
+
+   pub fn save(&self) -> Result<(), Error> {
+      let Some(path) = config_path() else {
+         return Ok(());
+      };
+
+      if let Some(dir) = path.parent() {
+         std::fs::create_dir_all(dir).map_err(|e| Error::write(dir, e))?;
+      }
+
+      let contents = toml::to_string(self).map_err(|e| Error::InvalidFormat(e.to_string()))?;
+      write_file(&path, Overwrite::Replace, |writer| {
+         writer.write_all(contents.as_bytes())?;
+         Ok(())
+      })?;
+      Ok(())
+   }
+}
+
+/// The settings file in the platform config directory, `$XDG_CONFIG_HOME/ncrypt` or `~/.config/ncrypt` on Linux
+fn config_path() -> Option<PathBuf> {
+   #[cfg(windows)]
+   let base = std::env::var_os("APPDATA").map(PathBuf::from);
+
+   #[cfg(target_os = "macos")]
+   let base = std::env::var_os("HOME").map(|home| PathBuf::from(home).join("Library/Application Support"));
+
+   #[cfg(all(unix, not(target_os = "macos")))]
+   let base = std::env::var_os("XDG_CONFIG_HOME")
+      .map(PathBuf::from)
+      .filter(|dir| dir.is_absolute())
+      .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")));
+
+   base.map(|base| base.join("ncrypt").join(FILE_NAME))
+}
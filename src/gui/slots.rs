@@ -0,0 +1,204 @@
+use crate::core::{Recipient, slots::SlotInfo};
+use eframe::egui::{Align, FontId, Layout, RichText, TextEdit, Ui, vec2};
+use ncrypt_me::secure_types::SecureString;
+use std::path::PathBuf;
+use zeus_theme::Theme;
+use zeus_ui_components::CredentialsForm;
+use zeus_widgets::{Button, Modal};
+
+/// What the user asked to change about the slots of a file
+pub enum SlotChange {
+   /// The username and password to add
+   AddPassword(SecureString, SecureString),
+   AddRecipient(Recipient),
+   Remove(usize),
+   /// Replaces the password of the main form with this username and password
+   ChangePassword(SecureString, SecureString),
+}
+
+/// Lists the slots of a single file and lets the user add and remove them or change its password
+///
+/// The file is unlocked with whatever the main form would decrypt it with.
+pub struct SlotsUi {
+   pub open: bool,
+   pub path: Option<PathBuf>,
+   pub slots: Vec<SlotInfo>,
+   /// Why the slots couldn't be read
+   pub error: Option<String>,
+   /// The password to add
+   pub credentials_form: CredentialsForm,
+   /// Give a changed password the Argon2 parameters of the settings instead of the old ones
+   pub new_argon2: bool,
+   /// The public key to add
+   pub recipient_input: String,
+   /// Whether a change is being written
+   pub busy: bool,
+}
+
+impl Default for SlotsUi {
+   fn default() -> Self {
+      Self::new()
+   }
+}
+
+impl SlotsUi {
+   pub fn new() -> Self {
+      let form = CredentialsForm::new()
+         .with_open(true)
+         .with_confirm_password(true);
+      Self {
+         open: false,
+         path: None,
+         slots: Vec::new(),
+         error: None,
+         credentials_form: form,
+         new_argon2: false,
+         recipient_input: String::new(),
+         busy: false,
+      }
+   }
+
+   pub fn set_slots(&mut self, slots: Result<Vec<SlotInfo>, String>) {
+      match slots {
+         Ok(slots) => {
+            self.slots = slots;
+            self.error = None;
+         }
+         Err(e) => {
+            self.slots.clear();
+            self.error = Some(e);
+         }
+      }
+   }
+
+   /// Returns the change the user asked for, if any
+   pub fn show(&mut self, theme: &Theme, ui: &mut Ui) -> Option<SlotChange> {
+      if !self.open {
+         return None;
+      }
+
+      let mut change = None;
+      let mut open = self.open;
+
+      Modal::new("key_slots", &mut open).show(ui.ctx(), |ui| {
+         ui.set_width(400.0);
+
+         ui.vertical_centered(|ui| {
+            ui.spacing_mut().item_spacing.y = 15.0;
+            ui.spacing_mut().button_padding = vec2(10.0, 8.0);
+
+            ui.label(RichText::new("Key Slots").size(theme.text_sizes.large));
+            ui.label(
+               RichText::new("The file is unlocked with the credentials, keyfiles or identity of the main form")
+                  .size(theme.text_sizes.small),
+            );
+
+            if let Some(e) = &self.error {
+               ui.label(RichText::new(e).size(theme.text_sizes.normal));
+            }
+
+            for (i, slot) in self.slots.iter().enumerate() {
+               ui.horizontal(|ui| {
+                  let text = format!("{}: {}", i + 1, slot);
+                  ui.label(RichText::new(text).size(theme.text_sizes.normal));
+
+                  ui.with_layout(Layout::right_to_left(Align::Center), |ui| {
+                     let text = RichText::new("Remove").size(theme.text_sizes.small);
+                     let button = Button::new(text).visuals(theme.button_visuals());
+                     let enabled = !self.busy && self.slots.len() > 1;
+                     if ui.add_enabled(enabled, button).clicked() {
+                        change = Some(SlotChange::Remove(i));
+                     }
+                  });
+               });
+            }
+
+            ui.add_space(10.0);
+            ui.label(RichText::new("New Password").size(theme.text_sizes.normal));
+
+            ui.scope(|ui| {
+               ui.spacing_mut().button_padding = vec2(4.0, 4.0);
+               self.credentials_form.show(theme, ui);
+            });
+
+            let text = RichText::new("Add Password").size(theme.text_sizes.normal);
+            let button = Button::new(text).visuals(theme.button_visuals());
+            let clicked = ui
+               .add_enabled(!self.busy, button)
+               .on_hover_text("Uses the keyfiles and Argon2 parameters of the main form")
+               .clicked();
+
+            if clicked {
+               change = Some(SlotChange::AddPassword(
+                  self.credentials_form.username(),
+                  self.credentials_form.password(),
+               ));
+            }
+
+            let text = RichText::new("Change Password").size(theme.text_sizes.normal);
+            let button = Button::new(text).visuals(theme.button_visuals());
+            let clicked = ui
+               .add_enabled(!self.busy, button)
+               .on_hover_text(
+                  "Replace the password of the main form with this one, the file is re-encrypted in memory under a new data key",
+               )
+               .clicked();
+
+            if self.slots.len() > 1 {
+               ui.label(
+                  RichText::new(
+                     "With more than one key slot only the password is replaced, the data key stays the same. \
+                     Remove the other slots first to re-encrypt the file under a new one.",
+                  )
+                  .size(theme.text_sizes.small),
+               );
+            }
+
+            if clicked {
+               change = Some(SlotChange::ChangePassword(
+                  self.credentials_form.username(),
+                  self.credentials_form.password(),
+               ));
+            }
+
+            let text = RichText::new("Use the current Argon2 parameters").size(theme.text_sizes.normal);
+            ui.checkbox(&mut self.new_argon2, text)
+               .on_hover_text("Otherwise the changed password keeps the parameters of the old one");
+
+            ui.add_space(10.0);
+            let size = vec2(ui.available_width(), 30.0);
+            ui.allocate_ui_with_layout(size, Layout::left_to_right(Align::Center), |ui| {
+               ui.spacing_mut().item_spacing.x = 20.0;
+
+               let text_edit = TextEdit::singleline(&mut self.recipient_input)
+                  .hint_text("ncryptpk...")
+                  .desired_width(ui.available_width() * 0.6)
+                  .font(FontId::proportional(theme.text_sizes.normal));
+               ui.add(text_edit);
+
+               let text = RichText::new("Add Recipient").size(theme.text_sizes.normal);
+               let button = Button::new(text).visuals(theme.button_visuals());
+               if ui.add_enabled(!self.busy, button).clicked() {
+                  match self.recipient_input.parse::<Recipient>() {
+                     Ok(recipient) => change = Some(SlotChange::AddRecipient(recipient)),
+                     Err(e) => self.error = Some(e.to_string()),
+                  }
+               }
+            });
+         });
+      });
+
+      if !open {
+         self.close();
+      }
+
+      change
+   }
+
+   pub fn close(&mut self) {
+      self.open = false;
+      self.path = None;
+      self.credentials_form.erase();
+      self.recipient_input.clear();
+   }
+}
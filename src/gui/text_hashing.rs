@@ -1,11 +1,16 @@
+use super::recovery::scan_qr;
 use eframe::egui::{FontId, Margin, RichText, Ui, vec2};
 use ncrypt_me::secure_types::SecureString;
+use serde::{Deserialize, Serialize};
 use sha3::{Digest, Sha3_224, Sha3_256, Sha3_384, Sha3_512};
+use std::fmt;
 use zeus_theme::Theme;
+#[cfg(target_os = "linux")]
+use zeus_ui_components::QRScanner;
 use zeus_widgets::SecureTextEdit;
 use zeus_widgets::{Button, ComboBox, Label};
 
-#[derive(Clone, PartialEq, Eq)]
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub enum HashAlgorithm {
    Sha3_224,
    Sha3_256,
@@ -13,17 +18,18 @@ pub enum HashAlgorithm {
    Sha3_512,
 }
 
-impl HashAlgorithm {
-   pub fn to_string(&self) -> String {
-      (match self {
+impl fmt::Display for HashAlgorithm {
+   fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+      f.write_str(match self {
          HashAlgorithm::Sha3_224 => "SHA3-224",
          HashAlgorithm::Sha3_256 => "SHA3-256",
          HashAlgorithm::Sha3_384 => "SHA3-384",
          HashAlgorithm::Sha3_512 => "SHA3-512",
       })
-      .to_string()
    }
+}
 
+impl HashAlgorithm {
    pub fn to_vec(&self) -> Vec<HashAlgorithm> {
       vec![
          HashAlgorithm::Sha3_224,
@@ -39,6 +45,16 @@ pub struct TextHashingUi {
    pub algorithm: HashAlgorithm,
    pub input_text: SecureString,
    pub output_hash: SecureString,
+   /// Why reading a QR code picture failed
+   pub scan_error: Option<String>,
+   #[cfg(target_os = "linux")]
+   pub scanner: QRScanner,
+}
+
+impl Default for TextHashingUi {
+   fn default() -> Self {
+      Self::new()
+   }
 }
 
 impl TextHashingUi {
@@ -48,6 +64,9 @@ impl TextHashingUi {
          algorithm: HashAlgorithm::Sha3_224,
          input_text: SecureString::new_with_capacity(1024).unwrap(),
          output_hash: SecureString::new_with_capacity(1024).unwrap(),
+         scan_error: None,
+         #[cfg(target_os = "linux")]
+         scanner: QRScanner::new(),
       }
    }
 
@@ -83,6 +102,27 @@ impl TextHashingUi {
             }
          });
 
+         ui.horizontal(|ui| {
+            let text = RichText::new("QR Picture").size(theme.text_sizes.normal);
+            let button = Button::new(text).visuals(theme.button_visuals());
+            if ui
+               .add(button)
+               .on_hover_text("Hash the text in the QR code of a picture")
+               .clicked()
+            {
+               should_calculate |= self.scan_picture();
+            }
+
+            #[cfg(target_os = "linux")]
+            {
+               should_calculate |= self.scan_screen(theme, ui);
+            }
+         });
+
+         if let Some(e) = &self.scan_error {
+            ui.label(RichText::new(e).size(theme.text_sizes.small));
+         }
+
          if should_calculate {
             self.calculate_hash();
          }
@@ -117,6 +157,55 @@ impl TextHashingUi {
       });
    }
 
+   /// Replaces the input with the text of the QR codes in a picture, one per line, returns whether it did
+   fn scan_picture(&mut self) -> bool {
+      let texts = match scan_qr() {
+         Ok(texts) => texts,
+         Err(e) => {
+            self.scan_error = Some(e.to_string());
+            return false;
+         }
+      };
+
+      self.scan_error = None;
+      if texts.is_empty() {
+         return false;
+      }
+
+      self.input_text.erase();
+      for (i, text) in texts.iter().enumerate() {
+         if i > 0 {
+            self.input_text.push_str("\n");
+         }
+         self.input_text.push_str(text);
+      }
+      true
+   }
+
+   /// Replaces the input with the text of a QR code on the screen, returns whether it did
+   #[cfg(target_os = "linux")]
+   fn scan_screen(&mut self, theme: &Theme, ui: &mut Ui) -> bool {
+      let text = RichText::new("Scan QR").size(theme.text_sizes.normal);
+      let button = Button::new(text).visuals(theme.button_visuals());
+      if ui
+         .add(button)
+         .on_hover_text("Point the mouse at a QR code on the screen to hash its text")
+         .clicked()
+      {
+         self.scanner.open(ui.ctx().clone());
+      }
+
+      self.scanner.show(ui.ctx());
+      let Some(text) = self.scanner.get_result() else {
+         return false;
+      };
+      self.scanner.reset();
+
+      self.scan_error = None;
+      self.input_text = text;
+      true
+   }
+
    pub fn calculate_hash(&mut self) {
       self.input_text.unlock_str(|input_text| {
          if input_text.is_empty() {
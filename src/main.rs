@@ -1,13 +1,11 @@
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")] // hide console window on Windows in release
 
-pub mod gui;
-
 use eframe::{
    egui,
    egui_wgpu::{WgpuConfiguration, WgpuSetup, WgpuSetupCreateNew},
    wgpu::{self, InstanceDescriptor, MemoryHints, Trace},
 };
-use gui::app::NCryptApp;
+use n_crypt::gui::app::NCryptApp;
 use std::sync::Arc;
 
 fn main() -> Result<(), eframe::Error> {
@@ -0,0 +1,95 @@
+mod common;
+
+use age::{
+   armor::{ArmoredWriter, Format},
+   secrecy::SecretString,
+   x25519,
+};
+use common::{options, write};
+use n_crypt::core::{
+   Progress,
+   age_format::{self, AgeSecret, AgeTarget},
+   decrypt_file,
+};
+use ncrypt_me::secure_types::SecureString;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use tempfile::tempdir;
+
+const CONTENTS: &[u8] = b"exchanged with age users";
+
+/// Encrypts `CONTENTS` as `plain.txt.age` in `dir` with the age crate itself
+fn age_encrypt(dir: &Path, recipient: &dyn age::Recipient, format: Format) -> PathBuf {
+   let path = dir.join("plain.txt.age");
+   let encryptor = age::Encryptor::with_recipients(std::iter::once(recipient)).unwrap();
+   let file = std::fs::File::create(&path).unwrap();
+   let armored = ArmoredWriter::wrap_output(file, format).unwrap();
+   let mut writer = encryptor.wrap_output(armored).unwrap();
+   writer.write_all(CONTENTS).unwrap();
+   writer.finish().unwrap().finish().unwrap();
+   path
+}
+
+#[test]
+fn age_decrypts_files_for_recipients() {
+   let dir = tempdir().unwrap();
+   let path = write(dir.path(), "plain.txt", CONTENTS);
+   let identity = x25519::Identity::generate();
+
+   let target = AgeTarget::Recipients(vec![identity.to_public()]);
+   let encrypted = age_format::encrypt_file(&target, &path, &options(), &Progress::new()).unwrap();
+   assert!(age_format::is_age_file(&encrypted).unwrap());
+
+   let bytes = std::fs::read(&encrypted).unwrap();
+   let decryptor = age::Decryptor::new(&bytes[..]).unwrap();
+   let mut reader = decryptor
+      .decrypt(std::iter::once(&identity as &dyn age::Identity))
+      .unwrap();
+   let mut decrypted = Vec::new();
+   reader.read_to_end(&mut decrypted).unwrap();
+   assert_eq!(decrypted, CONTENTS);
+}
+
+#[test]
+fn decrypts_armored_files_from_age() {
+   let dir = tempdir().unwrap();
+   let identity = x25519::Identity::generate();
+   let path = age_encrypt(dir.path(), &identity.to_public(), Format::AsciiArmor);
+
+   let header = age_format::read_header(&path).unwrap();
+   assert_eq!(header.recipients, 1);
+   assert!(!header.passphrase);
+
+   let secret = AgeSecret::Identities(vec![identity]);
+   let decrypted = decrypt_file(&path, secret, &options()).unwrap();
+   assert_eq!(decrypted, dir.path().join("plain.txt"));
+   assert_eq!(std::fs::read(decrypted).unwrap(), CONTENTS);
+}
+
+#[test]
+fn decrypts_passphrase_files_from_age() {
+   let dir = tempdir().unwrap();
+
+   // A low work factor keeps scrypt fast, decrypting accepts it all the same
+   let mut recipient = age::scrypt::Recipient::new(SecretString::from("passphrase".to_string()));
+   recipient.set_work_factor(10);
+   let path = age_encrypt(dir.path(), &recipient, Format::Binary);
+
+   let wrong = AgeSecret::Passphrase(SecureString::from("wrong".to_string()));
+   assert!(decrypt_file(&path, wrong, &options()).is_err());
+
+   let secret = AgeSecret::Passphrase(SecureString::from("passphrase".to_string()));
+   let decrypted = decrypt_file(&path, secret, &options()).unwrap();
+   assert_eq!(std::fs::read(decrypted).unwrap(), CONTENTS);
+}
+
+#[test]
+fn identities_of_others_fail() {
+   let dir = tempdir().unwrap();
+   let recipient = x25519::Identity::generate().to_public();
+   let path = age_encrypt(dir.path(), &recipient, Format::Binary);
+
+   let secret = AgeSecret::Identities(vec![x25519::Identity::generate()]);
+   assert!(decrypt_file(&path, secret, &options()).is_err());
+   assert!(!dir.path().join("plain.txt").exists());
+}
@@ -0,0 +1,56 @@
+mod common;
+
+use common::{argon2, credentials, options, write};
+use n_crypt::core::{Overwrite, archive, decrypt_file, encrypt_dir};
+use tempfile::tempdir;
+
+#[test]
+fn directories_round_trip() {
+   let dir = tempdir().unwrap();
+   let folder = dir.path().join("folder");
+   std::fs::create_dir_all(folder.join("nested")).unwrap();
+   write(&folder, "a.txt", b"first");
+   write(&folder.join("nested"), "b.txt", b"second");
+   write(&folder, "empty", b"");
+
+   let encrypted = encrypt_dir(
+      argon2(),
+      &folder,
+      credentials("user", "password"),
+      &options(),
+   )
+   .unwrap();
+   std::fs::remove_dir_all(&folder).unwrap();
+
+   let decrypted = decrypt_file(&encrypted, credentials("user", "password"), &options()).unwrap();
+   assert_eq!(decrypted, folder);
+   assert_eq!(std::fs::read(folder.join("a.txt")).unwrap(), b"first");
+   assert_eq!(
+      std::fs::read(folder.join("nested").join("b.txt")).unwrap(),
+      b"second"
+   );
+   assert!(std::fs::read(folder.join("empty")).unwrap().is_empty());
+}
+
+#[test]
+fn archive_entries_stay_inside_the_directory() {
+   let dir = tempdir().unwrap();
+
+   let mut builder = tar::Builder::new(Vec::new());
+   let contents = b"outside";
+   let mut header = tar::Header::new_gnu();
+   let name = b"../evil.txt";
+   header.as_gnu_mut().unwrap().name[..name.len()].copy_from_slice(name);
+   header.set_size(contents.len() as u64);
+   header.set_mode(0o644);
+   header.set_cksum();
+   builder.append(&header, &contents[..]).unwrap();
+   let tar = builder.into_inner().unwrap();
+
+   // The entry is skipped, nothing lands next to the directory
+   let target = dir.path().join("out");
+   archive::unpack(&tar[..], &target, Overwrite::Never).unwrap();
+
+   assert!(!dir.path().join("evil.txt").exists());
+   assert_eq!(std::fs::read_dir(&target).unwrap().count(), 0);
+}
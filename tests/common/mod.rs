@@ -0,0 +1,67 @@
+#![allow(dead_code)]
+
+use n_crypt::core::{
+   Options, Progress, RecoveryKey, Secret, decrypt_file, derive_key, encrypt_file, encrypt_file_with_key,
+};
+use ncrypt_me::{Argon2, Credentials, secure_types::SecureString};
+use std::path::{Path, PathBuf};
+
+/// Cheap parameters, the tests are about the format and not about Argon2
+pub fn argon2() -> Argon2 {
+   Argon2::new(1024, 1, 1)
+}
+
+pub fn credentials(username: &str, password: &str) -> Credentials {
+   let password = SecureString::from(password.to_string());
+   Credentials::new(
+      SecureString::from(username.to_string()),
+      password.clone(),
+      password,
+   )
+}
+
+pub fn options() -> Options {
+   Options {
+      ignore_memory_limit: true,
+      ..Default::default()
+   }
+}
+
+pub fn write(dir: &Path, name: &str, contents: &[u8]) -> PathBuf {
+   let path = dir.join(name);
+   std::fs::write(&path, contents).unwrap();
+   path
+}
+
+/// Writes `contents` as `name` in `dir`, encrypts it for user/password and removes the original
+pub fn encrypt(dir: &Path, name: &str, contents: &[u8]) -> PathBuf {
+   let path = write(dir, name, contents);
+   let encrypted = encrypt_file(argon2(), &path, credentials("user", "password"), &options()).unwrap();
+   std::fs::remove_file(&path).unwrap();
+   encrypted
+}
+
+/// Decrypts `encrypted` with `secret`, checks the contents and removes the decrypted copy again
+pub fn assert_decrypts(encrypted: &Path, secret: impl Into<Secret>, contents: &[u8]) {
+   let decrypted = decrypt_file(encrypted, secret, &options()).unwrap();
+   assert_eq!(std::fs::read(&decrypted).unwrap(), contents);
+   std::fs::remove_file(decrypted).unwrap();
+}
+
+/// Writes `contents` as `plain.txt` in `dir`, encrypts it with a password and a recovery slot and removes the original
+pub fn encrypt_with_recovery(dir: &Path, contents: &[u8], recovery_key: &RecoveryKey) -> PathBuf {
+   let path = write(dir, "plain.txt", contents);
+   let progress = Progress::new();
+   let mut key = derive_key(
+      argon2(),
+      credentials("user", "password"),
+      &options(),
+      &progress,
+   )
+   .unwrap();
+   key.add_recovery(recovery_key).unwrap();
+
+   let encrypted = encrypt_file_with_key(&key, &path, &options(), &progress).unwrap();
+   std::fs::remove_file(path).unwrap();
+   encrypted
+}
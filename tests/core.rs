@@ -0,0 +1,36 @@
+mod common;
+
+use common::{argon2, assert_decrypts, credentials, encrypt, options};
+use n_crypt::core::{Error, decrypt_bytes, decrypt_file, encrypt_bytes};
+use tempfile::tempdir;
+
+#[test]
+fn bytes_round_trip() {
+   let encrypted = encrypt_bytes(argon2(), b"secret", credentials("user", "password")).unwrap();
+   let decrypted = decrypt_bytes(encrypted.clone(), credentials("user", "password")).unwrap();
+   assert_eq!(decrypted, b"secret");
+
+   let res = decrypt_bytes(encrypted, credentials("user", "wrong"));
+   assert!(matches!(res, Err(Error::Decrypt(_))));
+}
+
+#[test]
+fn files_round_trip() {
+   let dir = tempdir().unwrap();
+   let encrypted = encrypt(dir.path(), "plain.txt", b"secret");
+   assert_eq!(encrypted, dir.path().join("plain.txt.ncrypt"));
+
+   assert_decrypts(&encrypted, credentials("user", "password"), b"secret");
+}
+
+#[test]
+fn wrong_credentials_fail() {
+   let dir = tempdir().unwrap();
+   let encrypted = encrypt(dir.path(), "plain.txt", b"secret");
+
+   let res = decrypt_file(&encrypted, credentials("user", "wrong"), &options());
+   assert!(matches!(res, Err(Error::Decrypt(_))));
+   let res = decrypt_file(&encrypted, credentials("other", "password"), &options());
+   assert!(matches!(res, Err(Error::Decrypt(_))));
+   assert!(!dir.path().join("plain.txt").exists());
+}
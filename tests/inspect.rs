@@ -0,0 +1,29 @@
+mod common;
+
+use common::{argon2, credentials, write};
+use n_crypt::core::{Error, encrypt_bytes, inspect, inspect::Format};
+use tempfile::tempdir;
+
+#[test]
+fn single_shot_files_are_inspected_from_their_header() {
+   let dir = tempdir().unwrap();
+   let encrypted = encrypt_bytes(argon2(), &[7u8; 4096], credentials("user", "password")).unwrap();
+   let path = write(dir.path(), "old.ncrypt", &encrypted);
+
+   let info = inspect(&path).unwrap();
+   assert_eq!(info.format, Format::SingleShot);
+   assert_eq!(info.argon2.unwrap().m_cost, 1024);
+   assert_eq!(info.file_size, encrypted.len() as u64);
+   assert!(info.payload_size >= 4096 && info.payload_size < info.file_size);
+}
+
+#[test]
+fn truncated_single_shot_headers_fail() {
+   let dir = tempdir().unwrap();
+   let encrypted = encrypt_bytes(argon2(), b"secret", credentials("user", "password")).unwrap();
+
+   for len in [4, 40] {
+      let path = write(dir.path(), "truncated.ncrypt", &encrypted[..len]);
+      assert!(matches!(inspect(&path), Err(Error::InvalidFormat(_))));
+   }
+}
@@ -0,0 +1,61 @@
+mod common;
+
+use common::{argon2, write};
+use n_crypt::core::{
+   Options, decrypt_file, encrypt_file,
+   keyfile::{self, Keyfile},
+};
+use ncrypt_me::{Credentials, secure_types::SecureString};
+use tempfile::tempdir;
+use zeroize::Zeroizing;
+
+fn credentials(keyfiles: &[Keyfile]) -> Credentials {
+   let username = SecureString::from("user");
+   keyfile::credentials(username, SecureString::from("password"), keyfiles).unwrap()
+}
+
+#[test]
+fn keyfiles_are_needed_to_decrypt() {
+   let dir = tempdir().unwrap();
+   let first = Keyfile::Path(write(dir.path(), "first.key", b"first keyfile"));
+   let second = Keyfile::Path(write(dir.path(), "second.key", b"second keyfile"));
+   let path = write(dir.path(), "plain.txt", b"contents");
+   let options = Options {
+      keyfile: true,
+      ..common::options()
+   };
+
+   let encrypted = encrypt_file(
+      argon2(),
+      &path,
+      credentials(&[first.clone(), second.clone()]),
+      &options,
+   )
+   .unwrap();
+   std::fs::remove_file(&path).unwrap();
+
+   assert!(
+      decrypt_file(
+         &encrypted,
+         credentials(std::slice::from_ref(&first)),
+         &options
+      )
+      .is_err()
+   );
+   assert!(decrypt_file(&encrypted, credentials(&[]), &common::options()).is_err());
+
+   // The order of the keyfiles doesn't matter
+   let decrypted = decrypt_file(&encrypted, credentials(&[second, first]), &options).unwrap();
+   assert_eq!(std::fs::read(decrypted).unwrap(), b"contents");
+}
+
+#[test]
+fn contents_count_as_a_file_holding_them() {
+   let dir = tempdir().unwrap();
+   let path = write(dir.path(), "scanned.key", b"printed as a QR code");
+   let contents = Keyfile::Contents(Zeroizing::new(b"printed as a QR code".to_vec()));
+
+   let from_file = keyfile::combined_digest(&[Keyfile::Path(path)]).unwrap();
+   let from_contents = keyfile::combined_digest(&[contents]).unwrap();
+   assert_eq!(from_file, from_contents);
+}
@@ -0,0 +1,88 @@
+mod common;
+
+use common::{argon2, credentials, options, write};
+use n_crypt::core::{Metadata, Options, Overwrite, Progress, batch::decrypt_batch, decrypt_file, encrypt_file};
+use std::path::Path;
+use tempfile::tempdir;
+
+/// Writes and reads back metadata carrying `name`
+fn round_trip(name: &str) -> Metadata {
+   let metadata = Metadata {
+      name: Some(name.to_string()),
+      ..Default::default()
+   };
+   let mut bytes = Vec::new();
+   metadata.write_to(&mut bytes).unwrap();
+   Metadata::read_from(&mut &bytes[..]).unwrap()
+}
+
+#[test]
+fn stored_names_must_be_plain_file_names() {
+   assert_eq!(round_trip("report.pdf").name.as_deref(), Some("report.pdf"));
+   assert_eq!(round_trip(".hidden").name.as_deref(), Some(".hidden"));
+
+   for name in ["../evil", "a/b", "..", ".", "a\\b", "/etc/passwd", ""] {
+      assert_eq!(round_trip(name).name, None, "kept the name {:?}", name);
+   }
+
+   let encrypted = Path::new("/data/secret.ncrypt");
+   assert_eq!(
+      round_trip("../evil").restored_path(encrypted),
+      Path::new("/data/secret")
+   );
+}
+
+#[test]
+fn neutral_names_restore_the_original_name() {
+   let dir = tempdir().unwrap();
+   let path = write(dir.path(), "report.pdf", b"contents");
+   let options = Options {
+      neutral_name: true,
+      ..options()
+   };
+
+   let encrypted = encrypt_file(argon2(), &path, credentials("user", "password"), &options).unwrap();
+   assert!(!encrypted.to_string_lossy().contains("report"));
+   std::fs::remove_file(&path).unwrap();
+
+   let decrypted = decrypt_file(&encrypted, credentials("user", "password"), &options).unwrap();
+   assert_eq!(decrypted, path);
+   assert_eq!(std::fs::read(decrypted).unwrap(), b"contents");
+}
+
+#[test]
+fn existing_stored_names_are_reported() {
+   let dir = tempdir().unwrap();
+   let path = write(dir.path(), "report.pdf", b"contents");
+   let options = Options {
+      neutral_name: true,
+      ..options()
+   };
+   let encrypted = encrypt_file(argon2(), &path, credentials("user", "password"), &options).unwrap();
+   let paths = [encrypted];
+
+   // The original is still there under the stored name, not under the name of the encrypted file
+   let summary = decrypt_batch(
+      &paths,
+      credentials("user", "password"),
+      &options,
+      &Progress::new(),
+      |_, _| {},
+   );
+   assert!(summary.succeeded.is_empty());
+   assert_eq!(summary.existing, vec![(paths[0].clone(), path.clone())]);
+
+   let options = Options {
+      overwrite: Overwrite::KeepBoth,
+      ..options
+   };
+   let summary = decrypt_batch(
+      &paths,
+      credentials("user", "password"),
+      &options,
+      &Progress::new(),
+      |_, _| {},
+   );
+   assert!(summary.existing.is_empty());
+   assert_eq!(summary.succeeded, vec![dir.path().join("report (1).pdf")]);
+}
@@ -0,0 +1,40 @@
+use image::{GrayImage, Luma};
+use n_crypt::core::{RecoveryKey, qr};
+use tempfile::tempdir;
+
+/// Pixels per module of the pictures
+const SCALE: u32 = 8;
+
+#[test]
+fn scans_recovery_keys_back_from_pictures() {
+   let dir = tempdir().unwrap();
+   let words = RecoveryKey::generate().to_words();
+
+   // A picture of the QR code with the quiet zone of 4 modules around it
+   let (width, modules) = qr::modules(&words).unwrap();
+   let side = (width as u32 + 8) * SCALE;
+   let picture = GrayImage::from_fn(side, side, |x, y| {
+      let (x, y) = ((x / SCALE) as i64 - 4, (y / SCALE) as i64 - 4);
+      let inside = (0..width as i64).contains(&x) && (0..width as i64).contains(&y);
+      let dark = inside && modules[y as usize * width + x as usize];
+      Luma([if dark { 0 } else { 255 }])
+   });
+   let path = dir.path().join("key.png");
+   picture.save(&path).unwrap();
+
+   let texts = qr::scan(&path).unwrap();
+   assert_eq!(texts.len(), 1);
+   assert_eq!(texts[0].as_str(), words.as_str());
+}
+
+#[test]
+fn pictures_without_a_qr_code_fail() {
+   let dir = tempdir().unwrap();
+   let path = dir.path().join("blank.png");
+   GrayImage::from_pixel(64, 64, Luma([255]))
+      .save(&path)
+      .unwrap();
+
+   assert!(qr::scan(&path).is_err());
+   assert!(qr::scan(&dir.path().join("missing.png")).is_err());
+}
@@ -0,0 +1,91 @@
+mod common;
+
+use common::{assert_decrypts, encrypt_with_recovery};
+use n_crypt::core::{
+   RecoveryKey,
+   shamir::{self, Share},
+};
+use tempfile::tempdir;
+
+#[test]
+fn any_threshold_of_shares_recover_the_key() {
+   let key = RecoveryKey::generate();
+   let shares = shamir::split(&key, 3, 5).unwrap();
+   assert_eq!(shares.len(), 5);
+
+   for (a, b, c) in [(0, 1, 2), (0, 2, 4), (1, 3, 4), (4, 3, 2)] {
+      let picked = [shares[a].clone(), shares[b].clone(), shares[c].clone()];
+      let recovered = shamir::combine(&picked).unwrap();
+      assert_eq!(recovered.as_bytes(), key.as_bytes());
+   }
+
+   let recovered = shamir::combine(&shares).unwrap();
+   assert_eq!(recovered.as_bytes(), key.as_bytes());
+}
+
+#[test]
+fn too_few_shares_fail() {
+   let shares = shamir::split(&RecoveryKey::generate(), 3, 5).unwrap();
+
+   assert!(shamir::combine(&shares[..2]).is_err());
+   // The same share twice counts once
+   assert!(shamir::combine(&[shares[0].clone(), shares[0].clone(), shares[1].clone()]).is_err());
+   assert!(shamir::combine(&[]).is_err());
+}
+
+#[test]
+fn shares_of_different_keys_are_rejected() {
+   let first = shamir::split(&RecoveryKey::generate(), 2, 3).unwrap();
+   let second = shamir::split(&RecoveryKey::generate(), 2, 3).unwrap();
+
+   assert!(shamir::combine(&[first[0].clone(), second[1].clone()]).is_err());
+}
+
+#[test]
+fn shares_survive_being_written_out() {
+   let key = RecoveryKey::generate();
+   let shares = shamir::split(&key, 2, 3).unwrap();
+
+   let parsed: Vec<Share> = shares
+      .iter()
+      .map(|share| share.to_string().parse().unwrap())
+      .collect();
+   assert!(parsed == shares);
+   assert_eq!(parsed[1].index(), 2);
+   assert_eq!(parsed[1].threshold(), 2);
+
+   let recovered = shamir::combine(&parsed[1..]).unwrap();
+   assert_eq!(recovered.as_bytes(), key.as_bytes());
+
+   assert!("ncryptshare-2-00".parse::<Share>().is_err());
+   assert!(
+      "ncryptshare-1-0011223344556677-0102"
+         .parse::<Share>()
+         .is_err()
+   );
+   assert!(
+      "ncryptshare-2-0011223344556677-0002"
+         .parse::<Share>()
+         .is_err()
+   );
+}
+
+#[test]
+fn invalid_splits_fail() {
+   let key = RecoveryKey::generate();
+   assert!(shamir::split(&key, 1, 3).is_err());
+   assert!(shamir::split(&key, 4, 3).is_err());
+}
+
+#[test]
+fn recovered_key_decrypts_the_file() {
+   let dir = tempdir().unwrap();
+   let recovery_key = RecoveryKey::generate();
+   let encrypted = encrypt_with_recovery(dir.path(), b"contents", &recovery_key);
+
+   let shares = shamir::split(&recovery_key, 2, 3).unwrap();
+   let recovered = shamir::combine(&shares[1..]).unwrap();
+   let recovered = RecoveryKey::from_words(&recovered.to_words()).unwrap();
+
+   assert_decrypts(&encrypted, recovered, b"contents");
+}
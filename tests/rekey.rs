@@ -0,0 +1,123 @@
+mod common;
+
+use common::{argon2, assert_decrypts, credentials, encrypt, encrypt_with_recovery, options};
+use n_crypt::core::{
+   Error, Progress, RecoveryKey, Secret,
+   batch::rekey_batch,
+   decrypt_file, rekey,
+   slots::{self, SlotInfo},
+   stream::Header,
+};
+use ncrypt_me::{Argon2, Credentials};
+use std::fs::File;
+use std::path::Path;
+use tempfile::tempdir;
+
+const CONTENTS: &[u8] = b"the contents of the file";
+
+fn nonce_prefix(path: &Path) -> Vec<u8> {
+   let mut file = File::open(path).unwrap();
+   Header::read_from(&mut file).unwrap().nonce_prefix.to_vec()
+}
+
+/// Changes the password of `encrypted` from `old` to `new`
+fn change_password(encrypted: &Path, old: Credentials, new: Credentials) -> Result<(), Error> {
+   rekey::change_credentials(
+      encrypted,
+      old,
+      None,
+      new,
+      false,
+      &options(),
+      &Progress::new(),
+   )
+}
+
+#[test]
+fn rekey_rotates_the_data_key_of_single_slot_files() {
+   let dir = tempdir().unwrap();
+   let encrypted = encrypt(dir.path(), "plain.txt", CONTENTS);
+   let before = nonce_prefix(&encrypted);
+
+   change_password(
+      &encrypted,
+      credentials("user", "password"),
+      credentials("user", "new"),
+   )
+   .unwrap();
+
+   assert_ne!(nonce_prefix(&encrypted), before);
+   assert!(decrypt_file(&encrypted, credentials("user", "password"), &options()).is_err());
+   assert_decrypts(&encrypted, credentials("user", "new"), CONTENTS);
+}
+
+#[test]
+fn rekey_keeps_the_other_slots() {
+   let dir = tempdir().unwrap();
+   let recovery_key = RecoveryKey::generate();
+   let encrypted = encrypt_with_recovery(dir.path(), CONTENTS, &recovery_key);
+   let before = nonce_prefix(&encrypted);
+
+   change_password(
+      &encrypted,
+      credentials("user", "password"),
+      credentials("user", "new"),
+   )
+   .unwrap();
+
+   assert_eq!(nonce_prefix(&encrypted), before);
+   assert!(decrypt_file(&encrypted, credentials("user", "password"), &options()).is_err());
+   assert_decrypts(&encrypted, credentials("user", "new"), CONTENTS);
+   assert_decrypts(&encrypted, recovery_key, CONTENTS);
+}
+
+#[test]
+fn rekey_with_wrong_credentials_leaves_the_file_alone() {
+   let dir = tempdir().unwrap();
+   let encrypted = encrypt_with_recovery(dir.path(), CONTENTS, &RecoveryKey::generate());
+   let before = std::fs::read(&encrypted).unwrap();
+
+   let res = change_password(
+      &encrypted,
+      credentials("user", "wrong"),
+      credentials("user", "new"),
+   );
+
+   assert!(matches!(res, Err(Error::Decrypt(_))));
+   assert_eq!(std::fs::read(&encrypted).unwrap(), before);
+}
+
+#[test]
+fn rekey_batch_reports_other_weak_passwords() {
+   let dir = tempdir().unwrap();
+   let encrypted = encrypt_with_recovery(dir.path(), CONTENTS, &RecoveryKey::generate());
+   slots::add_password(
+      &encrypted,
+      &Secret::from(credentials("user", "password")),
+      argon2(),
+      credentials("user", "second"),
+      &options(),
+      &Progress::new(),
+   )
+   .unwrap();
+
+   let stronger = Argon2::new(2048, 1, 1);
+   let summary = rekey_batch(
+      stronger,
+      std::slice::from_ref(&encrypted),
+      credentials("user", "password"),
+      &options(),
+      &Progress::new(),
+      |_, _| {},
+   );
+
+   // The password given is re-keyed, the other one is reported
+   assert_eq!(summary.succeeded, vec![encrypted.clone()]);
+   assert!(summary.failed.is_empty());
+   assert_eq!(summary.partial.len(), 1);
+   assert!(summary.partial[0].1.contains("another password"));
+   let kinds = slots::list(&encrypted).unwrap();
+   assert!(matches!(kinds[0], SlotInfo::Password(Some(ref argon2)) if argon2.m_cost == 2048));
+   assert_decrypts(&encrypted, credentials("user", "password"), CONTENTS);
+   assert_decrypts(&encrypted, credentials("user", "second"), CONTENTS);
+}
@@ -0,0 +1,37 @@
+mod common;
+
+use common::{argon2, credentials, options, write};
+use n_crypt::core::{Error, Options, encrypt_file};
+use std::path::{Path, PathBuf};
+use tempfile::tempdir;
+
+fn encrypt_and_shred(path: &Path) -> Result<PathBuf, Error> {
+   let options = Options {
+      shred: true,
+      ..options()
+   };
+   encrypt_file(argon2(), path, credentials("user", "password"), &options)
+}
+
+#[test]
+fn shreds_the_original_once_verified() {
+   let dir = tempdir().unwrap();
+   let path = write(dir.path(), "plain.txt", b"contents");
+
+   let encrypted = encrypt_and_shred(&path).unwrap();
+   assert!(encrypted.exists());
+   assert!(!path.exists());
+}
+
+#[cfg(unix)]
+#[test]
+fn refuses_originals_behind_a_symlink() {
+   let dir = tempdir().unwrap();
+   let target = write(dir.path(), "plain.txt", b"contents");
+   let link = dir.path().join("link.txt");
+   std::os::unix::fs::symlink(&target, &link).unwrap();
+
+   assert!(encrypt_and_shred(&link).is_err());
+   assert_eq!(std::fs::read(&target).unwrap(), b"contents");
+   assert!(!dir.path().join("link.txt.ncrypt").exists());
+}
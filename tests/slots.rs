@@ -0,0 +1,97 @@
+mod common;
+
+use common::{argon2, assert_decrypts, credentials, encrypt_with_recovery, options};
+use n_crypt::core::{
+   Identity, Progress, RecoveryKey, Secret, decrypt_file,
+   slots::{self, SlotInfo},
+};
+use tempfile::tempdir;
+
+const CONTENTS: &[u8] = b"the contents of the file";
+
+#[test]
+fn every_slot_kind_opens_the_file() {
+   let dir = tempdir().unwrap();
+   let recovery_key = RecoveryKey::generate();
+   let identity = Identity::generate();
+   let encrypted = encrypt_with_recovery(dir.path(), CONTENTS, &recovery_key);
+   let progress = Progress::new();
+
+   let password = Secret::from(credentials("user", "password"));
+   slots::add_recipient(
+      &encrypted,
+      &password,
+      &identity.recipient(),
+      &options(),
+      &progress,
+   )
+   .unwrap();
+   slots::add_password(
+      &encrypted,
+      &Secret::from(identity.clone()),
+      argon2(),
+      credentials("user", "second"),
+      &options(),
+      &progress,
+   )
+   .unwrap();
+
+   assert!(matches!(
+      slots::list(&encrypted).unwrap().as_slice(),
+      [
+         SlotInfo::Password(Some(_)),
+         SlotInfo::Recovery,
+         SlotInfo::Recipient,
+         SlotInfo::Password(Some(_))
+      ]
+   ));
+
+   assert_decrypts(&encrypted, credentials("user", "password"), CONTENTS);
+   assert_decrypts(&encrypted, credentials("user", "second"), CONTENTS);
+   assert_decrypts(&encrypted, identity, CONTENTS);
+   assert_decrypts(&encrypted, recovery_key, CONTENTS);
+
+   assert!(decrypt_file(&encrypted, Identity::generate(), &options()).is_err());
+   assert!(decrypt_file(&encrypted, RecoveryKey::generate(), &options()).is_err());
+}
+
+#[test]
+fn removed_slot_stops_working() {
+   let dir = tempdir().unwrap();
+   let recovery_key = RecoveryKey::generate();
+   let encrypted = encrypt_with_recovery(dir.path(), CONTENTS, &recovery_key);
+
+   let secret = Secret::from(recovery_key.clone());
+   slots::remove(&encrypted, 0, &secret, &options(), &Progress::new()).unwrap();
+
+   assert!(matches!(
+      slots::list(&encrypted).unwrap().as_slice(),
+      [SlotInfo::Recovery]
+   ));
+   assert!(decrypt_file(&encrypted, credentials("user", "password"), &options()).is_err());
+   assert_decrypts(&encrypted, recovery_key, CONTENTS);
+}
+
+#[cfg(unix)]
+#[test]
+fn editing_slots_keeps_permissions_and_mtime() {
+   use std::os::unix::fs::PermissionsExt;
+
+   let dir = tempdir().unwrap();
+   let encrypted = encrypt_with_recovery(dir.path(), CONTENTS, &RecoveryKey::generate());
+   std::fs::set_permissions(&encrypted, std::fs::Permissions::from_mode(0o640)).unwrap();
+   let before = std::fs::metadata(&encrypted).unwrap();
+
+   slots::add_recipient(
+      &encrypted,
+      &Secret::from(credentials("user", "password")),
+      &Identity::generate().recipient(),
+      &options(),
+      &Progress::new(),
+   )
+   .unwrap();
+
+   let after = std::fs::metadata(&encrypted).unwrap();
+   assert_eq!(after.permissions().mode() & 0o777, 0o640);
+   assert_eq!(after.modified().unwrap(), before.modified().unwrap());
+}
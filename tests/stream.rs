@@ -0,0 +1,93 @@
+mod common;
+
+use common::{assert_decrypts, credentials, encrypt, options};
+use n_crypt::core::{
+   Error, decrypt_file,
+   stream::{CHUNK_SIZE, Header},
+};
+use tempfile::tempdir;
+
+/// The on-disk size of every chunk but the last
+const SEALED_CHUNK_SIZE: usize = CHUNK_SIZE + 16;
+
+/// Bytes that differ from chunk to chunk, so swapped chunks can't decrypt to the same plaintext
+fn data(len: usize) -> Vec<u8> {
+   (0..len).map(|i| (i % 251) as u8).collect()
+}
+
+fn header_size(encrypted: &[u8]) -> usize {
+   Header::read_from(&mut &encrypted[..]).unwrap().size()
+}
+
+/// Decrypting must fail without leaving a decrypted file behind
+fn assert_fails(encrypted: &std::path::Path) {
+   let res = decrypt_file(encrypted, credentials("user", "password"), &options());
+   assert!(res.is_err(), "decrypted {:?}", encrypted);
+   assert!(!encrypted.with_extension("").exists());
+}
+
+#[test]
+fn round_trip_over_several_chunks() {
+   let dir = tempdir().unwrap();
+   let contents = data(2 * CHUNK_SIZE + 1000);
+   let encrypted = encrypt(dir.path(), "plain.bin", &contents);
+
+   assert_decrypts(&encrypted, credentials("user", "password"), &contents);
+}
+
+#[test]
+fn round_trip_empty_file() {
+   let dir = tempdir().unwrap();
+   let encrypted = encrypt(dir.path(), "plain.bin", &[]);
+
+   assert_decrypts(&encrypted, credentials("user", "password"), &[]);
+}
+
+#[test]
+fn truncated_file_fails() {
+   let dir = tempdir().unwrap();
+   let encrypted = encrypt(dir.path(), "plain.bin", &data(CHUNK_SIZE + 1000));
+   let bytes = std::fs::read(&encrypted).unwrap();
+   let header_size = header_size(&bytes);
+
+   // Cut off the last chunk, inside the last chunk, inside the header and right after it
+   for len in [
+      header_size + SEALED_CHUNK_SIZE,
+      bytes.len() - 1,
+      header_size - 1,
+      header_size,
+   ] {
+      std::fs::write(&encrypted, &bytes[..len]).unwrap();
+      assert_fails(&encrypted);
+   }
+}
+
+#[test]
+fn reordered_chunks_fail() {
+   let dir = tempdir().unwrap();
+   let encrypted = encrypt(dir.path(), "plain.bin", &data(2 * CHUNK_SIZE + 1000));
+   let mut bytes = std::fs::read(&encrypted).unwrap();
+   let start = header_size(&bytes);
+
+   let (first, rest) = bytes[start..].split_at_mut(SEALED_CHUNK_SIZE);
+   first.swap_with_slice(&mut rest[..SEALED_CHUNK_SIZE]);
+   std::fs::write(&encrypted, &bytes).unwrap();
+
+   let res = decrypt_file(&encrypted, credentials("user", "password"), &options());
+   assert!(matches!(res, Err(Error::Decrypt(_))));
+   assert!(!dir.path().join("plain.bin").exists());
+}
+
+#[test]
+fn tampered_header_fails() {
+   let dir = tempdir().unwrap();
+   let encrypted = encrypt(dir.path(), "plain.bin", b"secret");
+   let mut bytes = std::fs::read(&encrypted).unwrap();
+
+   // The last byte of the nonce prefix is authenticated with every chunk
+   let nonce_prefix_end = 8 + 1 + 1 + 4 + 19;
+   bytes[nonce_prefix_end - 1] ^= 1;
+   std::fs::write(&encrypted, &bytes).unwrap();
+
+   assert_fails(&encrypted);
+}